@@ -0,0 +1,475 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use alsa::card;
+use alsa::mixer::{MilliBel, Selem, SelemChannelId, SelemId};
+use alsa::{Ctl, Mixer, Round};
+
+use volume_sync_core::backend::Backend;
+use volume_sync_core::config::GroupConfig;
+use volume_sync_core::event::{VolumeSyncEvent, SHARED_CONTEXT};
+use volume_sync_core::sink::{DeviceId, DeviceKind, SinkDetails};
+use volume_sync_core::topology::CardDetails;
+use volume_sync_core::volume::{db_to_volume, volume_to_db, VolumeSpec, VOLUME_NORM};
+
+/// Milliseconds a card thread waits for mixer events before checking for writes
+const WAIT_MS: u32 = 100;
+
+/// A playback volume control of a card, e.g. `Master` or `PCM`
+#[derive(Clone, Debug)]
+struct Element {
+    card: u32,
+    name: String,
+    index: u32,
+    details: SinkDetails,
+}
+
+type Elements = Arc<Mutex<BTreeMap<u32, Element>>>;
+
+/// Position of every control seen on a card by name and index, kept for as long as the card is
+/// watched so a control keeps its device index when others come and go
+type Positions = BTreeMap<(String, u32), u32>;
+
+struct Write {
+    name: String,
+    index: u32,
+    volume: u32,
+}
+
+/// A card being watched
+struct Card {
+    id: String,
+    writes: Sender<Write>,
+    thread: JoinHandle<()>,
+}
+
+/// Keeps the playback volume controls of every ALSA card in sync, each card watched by a thread
+/// of its own that waits for mixer events. Controls are sinks named `<card id>.<control>`, e.g.
+/// `PCH.Master`.
+pub struct Mixers {
+    sender: Sender<VolumeSyncEvent>,
+    cards: Mutex<BTreeMap<u32, Card>>,
+    elements: Elements,
+    subscribed: Arc<AtomicBool>,
+}
+
+/// Index of the control at `position` on `card`, unique across cards
+fn device_index(card: u32, position: u32) -> u32 {
+    (card << 16) | position
+}
+
+/// Volume of a control on the server's scale, from its dB level when the driver reports one so
+/// cards with different ranges line up by loudness
+fn read_volume(selem: &Selem) -> Option<u32> {
+    let channels = SelemChannelId::all()
+        .iter()
+        .filter(|channel| selem.has_playback_channel(**channel));
+    let (min_db, max_db) = selem.get_playback_db_range();
+    if min_db < max_db {
+        let db = channels
+            .filter_map(|channel| selem.get_playback_vol_db(*channel).ok())
+            .max()?;
+        return Some(db_to_volume(db.to_db() as f64));
+    }
+    let (min, max) = selem.get_playback_volume_range();
+    let raw = channels
+        .filter_map(|channel| selem.get_playback_volume(*channel).ok())
+        .max()?;
+    let span = (max - min).max(1) as u64;
+    Some(((raw - min).max(0) as u64 * VOLUME_NORM as u64 / span) as u32)
+}
+
+/// Whether every channel of a control with a playback switch is switched off
+fn read_mute(selem: &Selem) -> bool {
+    selem.has_playback_switch()
+        && SelemChannelId::all()
+            .iter()
+            .filter(|channel| selem.has_playback_channel(**channel))
+            .all(|channel| selem.get_playback_switch(*channel).is_ok_and(|on| on == 0))
+}
+
+fn write_volume(selem: &Selem, volume: u32) -> alsa::Result<()> {
+    let (min_db, max_db) = selem.get_playback_db_range();
+    if min_db < max_db {
+        let db = volume_to_db(volume).clamp(min_db.to_db() as f64, max_db.to_db() as f64);
+        return selem.set_playback_db_all(MilliBel::from_db(db as f32), Round::Floor);
+    }
+    let (min, max) = selem.get_playback_volume_range();
+    let raw =
+        min + ((max - min) as u64 * volume.min(VOLUME_NORM) as u64 / VOLUME_NORM as u64) as i64;
+    selem.set_playback_volume_all(raw)
+}
+
+/// Every playback volume control of `card`, new ones taking the next free position
+fn list(
+    mixer: &Mixer,
+    card: u32,
+    card_id: &str,
+    card_name: &str,
+    positions: &mut Positions,
+) -> Vec<Element> {
+    mixer
+        .iter()
+        .filter_map(Selem::new)
+        .filter(Selem::has_playback_volume)
+        .filter_map(|selem| {
+            let id = selem.get_id();
+            let name = id.get_name().ok()?.to_string();
+            let index = id.get_index();
+            let volume = read_volume(&selem)?;
+            let next = positions.len() as u32;
+            let position = *positions.entry((name.clone(), index)).or_insert(next);
+            let device = if index > 0 {
+                format!("{card_id}.{name},{index}")
+            } else {
+                format!("{card_id}.{name}")
+            };
+            let properties = BTreeMap::from([
+                ("alsa.card".to_string(), card.to_string()),
+                ("alsa.card_name".to_string(), card_name.to_string()),
+                (
+                    "device.description".to_string(),
+                    format!("{card_name} {name}"),
+                ),
+            ]);
+            let details = SinkDetails {
+                index: device_index(card, position),
+                name: device,
+                description: Some(format!("{card_name} {name}")),
+                card_name: Some(card_name.to_string()),
+                kind: DeviceKind::Sink,
+                properties,
+                volume,
+                mute: read_mute(&selem),
+                active_port: None,
+                ports: Vec::new(),
+                card: Some(card),
+                suspended: false,
+                sample_spec: String::new(),
+                rate: 0,
+                formats: Vec::new(),
+                seat: None,
+                monitor: None,
+                flat_volume: false,
+                hw_volume: false,
+                volume_steps: 0,
+            };
+            Some(Element {
+                card,
+                name,
+                index,
+                details,
+            })
+        })
+        .collect()
+}
+
+/// The thread watching one card
+struct CardWatcher {
+    mixer: Mixer,
+    card: u32,
+    card_id: String,
+    card_name: String,
+    positions: Positions,
+    writes: Receiver<Write>,
+    elements: Elements,
+    sender: Sender<VolumeSyncEvent>,
+    subscribed: Arc<AtomicBool>,
+}
+
+impl CardWatcher {
+    /// Applies writes and reports changes of the card's controls until `writes` is closed or the
+    /// card is gone
+    fn run(mut self) {
+        let card = self.card;
+        loop {
+            loop {
+                match self.writes.try_recv() {
+                    Ok(write) => self.apply(write),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+            if let Err(e) = self.mixer.wait(Some(WAIT_MS)) {
+                if io::Error::from_raw_os_error(e.errno()).kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                tracing::warn!(card, "card is gone, no longer watching it: {e}");
+                self.remove();
+                return;
+            }
+            if let Err(e) = self.mixer.handle_events() {
+                tracing::warn!(card, "failed to read mixer events: {e}");
+            }
+            self.report();
+        }
+    }
+
+    fn apply(&self, write: Write) {
+        let card = self.card;
+        let Some(selem) = self
+            .mixer
+            .find_selem(&SelemId::new(&write.name, write.index))
+        else {
+            tracing::warn!(card, control = %write.name, "control is gone");
+            return;
+        };
+        if let Err(e) = write_volume(&selem, write.volume) {
+            tracing::warn!(card, control = %write.name, "failed to set volume: {e}");
+        }
+    }
+
+    fn send(&self, event: VolumeSyncEvent) {
+        if self.subscribed.load(Ordering::Relaxed) {
+            self.sender.send(event).expect("failed to send mixer event");
+        }
+    }
+
+    /// Sends an event for every control that appeared, changed volume, was muted or went away
+    fn report(&mut self) {
+        let current = list(
+            &self.mixer,
+            self.card,
+            &self.card_id,
+            &self.card_name,
+            &mut self.positions,
+        );
+        let mut known = self.elements.lock().expect("failed to lock mixer controls");
+        let gone: Vec<u32> = known
+            .values()
+            .filter(|e| e.card == self.card)
+            .map(|e| e.details.index)
+            .filter(|index| !current.iter().any(|e| e.details.index == *index))
+            .collect();
+        for index in gone {
+            known.remove(&index);
+            self.send(VolumeSyncEvent::SinkRemoved(index));
+        }
+        for element in current {
+            let details = element.details.clone();
+            let event = match known.insert(details.index, element) {
+                None => VolumeSyncEvent::SinkNew(details),
+                Some(previous) if previous.details != details => {
+                    VolumeSyncEvent::SinkChanged(details)
+                }
+                Some(_) => continue,
+            };
+            self.send(event);
+        }
+    }
+
+    /// Drops every control of a card that went away, along with the card itself
+    fn remove(&self) {
+        let mut known = self.elements.lock().expect("failed to lock mixer controls");
+        known.retain(|index, element| {
+            if element.card != self.card {
+                return true;
+            }
+            self.send(VolumeSyncEvent::SinkRemoved(*index));
+            false
+        });
+        self.send(VolumeSyncEvent::CardRemoved(self.card));
+    }
+}
+
+impl Mixers {
+    /// Opens the mixer of every card and starts watching it
+    pub fn connect(sender: Sender<VolumeSyncEvent>) -> Result<Mixers, String> {
+        let mixers = Mixers {
+            sender,
+            cards: Mutex::default(),
+            elements: Elements::default(),
+            subscribed: Arc::new(AtomicBool::new(true)),
+        };
+        mixers.enumerate()?;
+        if mixers
+            .cards
+            .lock()
+            .expect("failed to lock cards")
+            .is_empty()
+        {
+            return Err("no ALSA card with a mixer found".to_string());
+        }
+        let _ = mixers.sender.send(VolumeSyncEvent::ContextState(
+            SHARED_CONTEXT.to_string(),
+            "Ready".to_string(),
+        ));
+        Ok(mixers)
+    }
+
+    /// Starts watching every card that isn't watched yet, forgetting those whose thread ended as
+    /// the card went away
+    fn enumerate(&self) -> Result<(), String> {
+        let mut cards = self.cards.lock().expect("failed to lock cards");
+        cards.retain(|_, card| !card.thread.is_finished());
+        for card in card::Iter::new() {
+            let card = card.map_err(|e| format!("failed to list ALSA cards: {e}"))?;
+            let index = card.get_index() as u32;
+            if cards.contains_key(&index) {
+                continue;
+            }
+            if let Some(watched) = self.watch(&card, index) {
+                cards.insert(index, watched);
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens the mixer of a card and starts a thread watching it
+    fn watch(&self, card: &card::Card, index: u32) -> Option<Card> {
+        let card_id = Ctl::from_card(card, false)
+            .and_then(|ctl| Ok(ctl.card_info()?.get_id()?.to_string()))
+            .unwrap_or_else(|_| index.to_string());
+        let card_name = card.get_name().unwrap_or_else(|_| card_id.clone());
+        let mixer = match Mixer::new(&format!("hw:{index}"), false) {
+            Ok(mixer) => mixer,
+            Err(e) => {
+                tracing::warn!("failed to open the mixer of card {card_id}: {e}");
+                return None;
+            }
+        };
+        let mut positions = Positions::new();
+        let found = list(&mixer, index, &card_id, &card_name, &mut positions);
+        tracing::info!("card {card_id}: {} playback controls", found.len());
+        self.elements
+            .lock()
+            .expect("failed to lock mixer controls")
+            .extend(found.into_iter().map(|e| (e.details.index, e)));
+
+        let (writes, receiver) = channel();
+        let watcher = CardWatcher {
+            mixer,
+            card: index,
+            card_id: card_id.clone(),
+            card_name,
+            positions,
+            writes: receiver,
+            elements: self.elements.clone(),
+            sender: self.sender.clone(),
+            subscribed: self.subscribed.clone(),
+        };
+        Some(Card {
+            id: card_id,
+            writes,
+            thread: thread::spawn(move || watcher.run()),
+        })
+    }
+
+    fn volume(&self, device: DeviceId) -> Option<u32> {
+        let elements = self.elements.lock().expect("failed to lock mixer controls");
+        elements.get(&device.index).map(|e| e.details.volume)
+    }
+
+    fn write(&self, device: DeviceId, volume: u32) {
+        if device.kind != DeviceKind::Sink {
+            return;
+        }
+        let elements = self.elements.lock().expect("failed to lock mixer controls");
+        let Some(element) = elements.get(&device.index) else {
+            tracing::warn!(%device, "unknown mixer control");
+            return;
+        };
+        let cards = self.cards.lock().expect("failed to lock cards");
+        let Some(card) = cards.get(&element.card) else {
+            return;
+        };
+        let _ = card.writes.send(Write {
+            name: element.name.clone(),
+            index: element.index,
+            volume,
+        });
+    }
+}
+
+impl Backend for Mixers {
+    fn configure(&mut self, groups: &BTreeMap<String, GroupConfig>) {
+        for (group, group_config) in groups {
+            if group_config.isolated {
+                tracing::debug!("{group}: ALSA controls have no connections to isolate");
+            }
+            if !group_config.sources.is_empty() {
+                tracing::warn!("{group}: the ALSA backend only syncs playback controls");
+            }
+        }
+    }
+
+    /// Lists every card again first, picking up cards plugged in since
+    fn request_topology(&self) {
+        if let Err(e) = self.enumerate() {
+            tracing::warn!("{e}");
+        }
+        let cards = self
+            .cards
+            .lock()
+            .expect("failed to lock cards")
+            .iter()
+            .map(|(index, card)| CardDetails {
+                index: *index,
+                name: card.id.clone(),
+                active_profile: None,
+                profiles: Vec::new(),
+            })
+            .collect();
+        let sinks = self
+            .elements
+            .lock()
+            .expect("failed to lock mixer controls")
+            .values()
+            .map(|e| e.details.clone())
+            .collect();
+        for event in [
+            VolumeSyncEvent::CardListLoaded(cards),
+            VolumeSyncEvent::SinkListLoaded(sinks),
+            VolumeSyncEvent::SourceListLoaded(Vec::new()),
+            VolumeSyncEvent::StreamListLoaded(Vec::new()),
+        ] {
+            self.sender.send(event).expect("failed to send listing");
+        }
+    }
+
+    fn sync_volume(&self, group: &str, from: DeviceId, to: &[DeviceId], min_delta: u32) {
+        let Some(volume) = self.volume(from) else {
+            tracing::warn!(%group, %from, "unknown mixer control");
+            return;
+        };
+        for device in to {
+            let delta = self
+                .volume(*device)
+                .map_or(u32::MAX, |v| v.abs_diff(volume));
+            if delta > 0 && delta >= min_delta {
+                self.write(*device, volume);
+            }
+        }
+    }
+
+    fn set_volume(&self, _group: &str, devices: &[DeviceId], volume: VolumeSpec) {
+        let Some(current) = devices.first().and_then(|first| self.volume(*first)) else {
+            return;
+        };
+        let volume = volume.apply(current);
+        for device in devices {
+            self.write(*device, volume);
+        }
+    }
+
+    fn restore_volume(&self, _group: &str, device: DeviceId, volume: u32) {
+        self.write(device, volume);
+    }
+
+    fn set_subscribed(&self, subscribed: bool) {
+        self.subscribed.store(subscribed, Ordering::Relaxed);
+    }
+
+    /// Stops every card thread once its pending writes are applied
+    fn shutdown(self: Box<Self>) {
+        let cards = self.cards.into_inner().expect("failed to lock cards");
+        for card in cards.into_values() {
+            drop(card.writes);
+            let _ = card.thread.join();
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use jack::{
+    AsyncClient, AudioIn, AudioOut, Client, ClientOptions, Control, MidiIn, Port, ProcessHandler,
+    ProcessScope,
+};
+
+use volume_sync_core::backend::Backend;
+use volume_sync_core::config::{Config, GroupConfig};
+use volume_sync_core::event::{VolumeSyncEvent, SHARED_CONTEXT};
+use volume_sync_core::sink::{DeviceId, DeviceKind, SinkDetails};
+use volume_sync_core::volume::{VolumeSpec, VOLUME_NORM};
+
+/// Name of the JACK client holding every gain stage
+const CLIENT_NAME: &str = "volume-sync";
+/// How often gain changes are looked for
+const REPORT_INTERVAL: Duration = Duration::from_millis(50);
+/// MIDI controller setting a stage's gain, channel volume
+const VOLUME_CONTROLLER: u8 = 7;
+
+/// Gain applied to the audio of one JACK client, on the same scale as sink volumes
+struct Stage {
+    client: String,
+    volume: AtomicU32,
+}
+
+/// Ports of one stage, only touched by the process callback
+struct StagePorts {
+    inputs: Vec<Port<AudioIn>>,
+    outputs: Vec<Port<AudioOut>>,
+    control: Port<MidiIn>,
+}
+
+struct Process {
+    stages: Arc<Vec<Stage>>,
+    ports: Vec<StagePorts>,
+}
+
+/// Amplitude factor for `volume`, using the server's cubic mapping
+fn amplitude(volume: u32) -> f32 {
+    let linear = volume as f32 / VOLUME_NORM as f32;
+    linear * linear * linear
+}
+
+impl ProcessHandler for Process {
+    fn process(&mut self, _: &Client, ps: &ProcessScope) -> Control {
+        for (stage, ports) in self.stages.iter().zip(self.ports.iter_mut()) {
+            for event in ports.control.iter(ps) {
+                if let [status, VOLUME_CONTROLLER, value] = *event.bytes {
+                    if status & 0xf0 == 0xb0 {
+                        let volume = value as u32 * VOLUME_NORM / 127;
+                        stage.volume.store(volume, Ordering::Relaxed);
+                    }
+                }
+            }
+            let gain = amplitude(stage.volume.load(Ordering::Relaxed));
+            for (input, output) in ports.inputs.iter().zip(ports.outputs.iter_mut()) {
+                let output = output.as_mut_slice(ps);
+                for (out, sample) in output.iter_mut().zip(input.as_slice(ps)) {
+                    *out = sample * gain;
+                }
+            }
+        }
+        Control::Continue
+    }
+}
+
+/// A gain stage for every configured JACK client, synced like sinks named after the client. Audio
+/// is routed through `volume-sync:<client>_in_<n>` and `volume-sync:<client>_out_<n>`, and a
+/// stage's gain follows channel volume messages on `volume-sync:<client>_gain`.
+pub struct Gains {
+    sender: Sender<VolumeSyncEvent>,
+    stages: Arc<Vec<Stage>>,
+    client: AsyncClient<(), Process>,
+    subscribed: Arc<AtomicBool>,
+    running: Arc<AtomicBool>,
+    reporter: JoinHandle<()>,
+}
+
+fn details(index: usize, stage: &Stage) -> SinkDetails {
+    SinkDetails {
+        index: index as u32,
+        name: stage.client.clone(),
+        description: Some(format!("{} gain", stage.client)),
+        card_name: None,
+        kind: DeviceKind::Sink,
+        properties: BTreeMap::from([(
+            "device.description".to_string(),
+            format!("{} gain", stage.client),
+        )]),
+        volume: stage.volume.load(Ordering::Relaxed),
+        mute: false,
+        active_port: None,
+        ports: Vec::new(),
+        card: None,
+        suspended: false,
+        sample_spec: String::new(),
+        rate: 0,
+        formats: Vec::new(),
+        seat: None,
+        monitor: None,
+        flat_volume: false,
+        hw_volume: false,
+        volume_steps: 0,
+    }
+}
+
+/// Sends a change event for every stage whose gain changed, until `running` is cleared
+fn report(
+    stages: Arc<Vec<Stage>>,
+    sender: Sender<VolumeSyncEvent>,
+    subscribed: Arc<AtomicBool>,
+    running: Arc<AtomicBool>,
+) {
+    let mut reported: Vec<u32> = stages
+        .iter()
+        .map(|stage| stage.volume.load(Ordering::Relaxed))
+        .collect();
+    while running.load(Ordering::Relaxed) {
+        thread::sleep(REPORT_INTERVAL);
+        for (index, stage) in stages.iter().enumerate() {
+            let volume = stage.volume.load(Ordering::Relaxed);
+            if volume == reported[index] {
+                continue;
+            }
+            reported[index] = volume;
+            if subscribed.load(Ordering::Relaxed) {
+                let _ = sender.send(VolumeSyncEvent::SinkChanged(details(index, stage)));
+            }
+        }
+    }
+}
+
+impl Gains {
+    /// Registers the ports of a stage for every client in `jack_clients` and starts processing
+    pub fn connect(sender: Sender<VolumeSyncEvent>, config: &Config) -> Result<Gains, String> {
+        if config.jack_clients.is_empty() {
+            return Err("no jack_clients configured".to_string());
+        }
+        let (client, _) = Client::new(CLIENT_NAME, ClientOptions::default())
+            .map_err(|e| format!("failed to open JACK client: {e}"))?;
+        let register = |e: jack::Error| format!("failed to register JACK port: {e}");
+        let mut stages = Vec::new();
+        let mut ports = Vec::new();
+        for name in &config.jack_clients {
+            let mut stage_ports = StagePorts {
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+                control: client
+                    .register_port(&format!("{name}_gain"), MidiIn::default())
+                    .map_err(register)?,
+            };
+            for channel in 1..=config.jack_channels {
+                stage_ports.inputs.push(
+                    client
+                        .register_port(&format!("{name}_in_{channel}"), AudioIn::default())
+                        .map_err(register)?,
+                );
+                stage_ports.outputs.push(
+                    client
+                        .register_port(&format!("{name}_out_{channel}"), AudioOut::default())
+                        .map_err(register)?,
+                );
+            }
+            stages.push(Stage {
+                client: name.clone(),
+                volume: AtomicU32::new(VOLUME_NORM),
+            });
+            ports.push(stage_ports);
+        }
+        tracing::info!("JACK gain stages for {}", config.jack_clients.join(", "));
+
+        let stages = Arc::new(stages);
+        let process = Process {
+            stages: stages.clone(),
+            ports,
+        };
+        let client = client
+            .activate_async((), process)
+            .map_err(|e| format!("failed to activate JACK client: {e}"))?;
+
+        let subscribed = Arc::new(AtomicBool::new(true));
+        let running = Arc::new(AtomicBool::new(true));
+        let reporter = thread::spawn({
+            let (stages, sender) = (stages.clone(), sender.clone());
+            let (subscribed, running) = (subscribed.clone(), running.clone());
+            move || report(stages, sender, subscribed, running)
+        });
+        let _ = sender.send(VolumeSyncEvent::ContextState(
+            SHARED_CONTEXT.to_string(),
+            "Ready".to_string(),
+        ));
+        Ok(Gains {
+            sender,
+            stages,
+            client,
+            subscribed,
+            running,
+            reporter,
+        })
+    }
+
+    fn stage(&self, device: DeviceId) -> Option<&Stage> {
+        if device.kind != DeviceKind::Sink {
+            return None;
+        }
+        self.stages.get(device.index as usize)
+    }
+
+    fn volume(&self, device: DeviceId) -> Option<u32> {
+        self.stage(device)
+            .map(|stage| stage.volume.load(Ordering::Relaxed))
+    }
+
+    fn write(&self, device: DeviceId, volume: u32) {
+        match self.stage(device) {
+            Some(stage) => stage.volume.store(volume, Ordering::Relaxed),
+            None => tracing::warn!(%device, "unknown gain stage"),
+        }
+    }
+}
+
+impl Backend for Gains {
+    fn configure(&mut self, groups: &BTreeMap<String, GroupConfig>) {
+        for (group, group_config) in groups {
+            if !group_config.sources.is_empty() {
+                tracing::warn!("{group}: the JACK backend only has gain stages for outputs");
+            }
+        }
+    }
+
+    fn request_topology(&self) {
+        let sinks = self
+            .stages
+            .iter()
+            .enumerate()
+            .map(|(index, stage)| details(index, stage))
+            .collect();
+        for event in [
+            VolumeSyncEvent::CardListLoaded(Vec::new()),
+            VolumeSyncEvent::SinkListLoaded(sinks),
+            VolumeSyncEvent::SourceListLoaded(Vec::new()),
+            VolumeSyncEvent::StreamListLoaded(Vec::new()),
+        ] {
+            self.sender.send(event).expect("failed to send listing");
+        }
+    }
+
+    fn sync_volume(&self, group: &str, from: DeviceId, to: &[DeviceId], min_delta: u32) {
+        let Some(volume) = self.volume(from) else {
+            tracing::warn!(%group, %from, "unknown gain stage");
+            return;
+        };
+        for device in to {
+            let delta = self
+                .volume(*device)
+                .map_or(u32::MAX, |v| v.abs_diff(volume));
+            if delta > 0 && delta >= min_delta {
+                self.write(*device, volume);
+            }
+        }
+    }
+
+    fn set_volume(&self, _group: &str, devices: &[DeviceId], volume: VolumeSpec) {
+        let Some(current) = devices.first().and_then(|first| self.volume(*first)) else {
+            return;
+        };
+        let volume = volume.apply(current);
+        for device in devices {
+            self.write(*device, volume);
+        }
+    }
+
+    fn restore_volume(&self, _group: &str, device: DeviceId, volume: u32) {
+        self.write(device, volume);
+    }
+
+    fn set_subscribed(&self, subscribed: bool) {
+        self.subscribed.store(subscribed, Ordering::Relaxed);
+    }
+
+    /// Stops reporting changes and closes the JACK client
+    fn shutdown(self: Box<Self>) {
+        self.running.store(false, Ordering::Relaxed);
+        let _ = self.reporter.join();
+        if let Err(e) = self.client.deactivate() {
+            tracing::warn!("failed to close JACK client: {e}");
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::mpsc::Sender;
+
+use pulse::mainloop::threaded::Mainloop;
+
+use volume_sync_core::backend::Backend;
+use volume_sync_core::config::GroupConfig;
+use volume_sync_core::event::VolumeSyncEvent;
+use volume_sync_core::sink::DeviceId;
+use volume_sync_core::volume::VolumeSpec;
+
+use crate::seat;
+use crate::volume_sync::{Modules, VolumeCache, VolumeSync};
+
+/// The shared server connection plus one dedicated connection per isolated group, each running
+/// on its own mainloop thread.
+pub struct Contexts {
+    sender: Sender<VolumeSyncEvent>,
+    cache: VolumeCache,
+    pub shared: VolumeSync,
+    isolated: BTreeMap<String, VolumeSync>,
+    /// Combine-sink module of every group that has one
+    modules: Modules,
+    /// Address of the server every context connects to, the default server if unset
+    server: Option<String>,
+}
+
+fn start(
+    sender: Sender<VolumeSyncEvent>,
+    cache: VolumeCache,
+    group: Option<String>,
+    server: Option<&str>,
+) -> Result<VolumeSync, &'static str> {
+    let mainloop = Rc::new(RefCell::new(
+        Mainloop::new().expect("failed to create mainloop"),
+    ));
+    let mut volume_sync = VolumeSync::new(mainloop.clone(), sender, cache, group, server);
+
+    tracing::info!(
+        "starting mainloop for context {}",
+        volume_sync.context_name()
+    );
+    mainloop.borrow_mut().lock();
+    mainloop
+        .borrow_mut()
+        .start()
+        .expect("failed to start mainloop");
+    mainloop.borrow_mut().unlock();
+    volume_sync.connect()?;
+    Ok(volume_sync)
+}
+
+impl Contexts {
+    /// Connects to the server chosen by the `server` option, see `seat::server_address`
+    pub fn connect(
+        sender: Sender<VolumeSyncEvent>,
+        server: Option<&str>,
+    ) -> Result<Contexts, String> {
+        let server = server.map(seat::server_address).transpose()?;
+        if let Some(server) = &server {
+            tracing::info!("using server {server}");
+        }
+        let cache = VolumeCache::default();
+        Ok(Contexts {
+            shared: start(sender.clone(), cache.clone(), None, server.as_deref())?,
+            sender,
+            cache,
+            isolated: BTreeMap::new(),
+            modules: Modules::default(),
+            server,
+        })
+    }
+
+    pub fn for_group(&self, group: &str) -> &VolumeSync {
+        self.isolated.get(group).unwrap_or(&self.shared)
+    }
+}
+
+impl Backend for Contexts {
+    /// Opens connections for newly isolated groups and closes those no longer needed
+    fn configure(&mut self, groups: &BTreeMap<String, GroupConfig>) {
+        let wanted: Vec<&String> = groups
+            .iter()
+            .filter(|(_, group_config)| group_config.isolated)
+            .map(|(group, _)| group)
+            .collect();
+
+        let stale: Vec<String> = self
+            .isolated
+            .keys()
+            .filter(|group| !wanted.contains(group))
+            .cloned()
+            .collect();
+        for group in stale {
+            if let Some(mut volume_sync) = self.isolated.remove(&group) {
+                volume_sync.disconnect();
+            }
+        }
+
+        for group in wanted {
+            if self.isolated.contains_key(group) {
+                continue;
+            }
+            let server = self.server.as_deref();
+            match start(
+                self.sender.clone(),
+                self.cache.clone(),
+                Some(group.clone()),
+                server,
+            ) {
+                Ok(volume_sync) => {
+                    self.isolated.insert(group.clone(), volume_sync);
+                }
+                Err(e) => {
+                    tracing::error!("{e} for group {group}, falling back to the shared context");
+                    let _ = self.sender.send(VolumeSyncEvent::ContextState(
+                        group.clone(),
+                        "Failed".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Asks the shared connection for every card, sink, source, recording and stream; the server
+    /// answers in order, so streams arrive last
+    fn request_topology(&self) {
+        tracing::debug!("fetch cards, sinks, sources, recordings and streams");
+        self.shared.request_cards();
+        self.shared.request_sinks();
+        self.shared.request_sources();
+        self.shared.request_recordings();
+        self.shared.request_streams();
+    }
+
+    fn sync_volume(&self, group: &str, from: DeviceId, to: &[DeviceId], min_delta: u32) {
+        self.for_group(group)
+            .sync_volume_to_many(from, to, min_delta);
+    }
+
+    fn set_volume(&self, group: &str, devices: &[DeviceId], volume: VolumeSpec) {
+        self.for_group(group).set_volume(devices, volume);
+    }
+
+    fn restore_volume(&self, group: &str, device: DeviceId, volume: u32) {
+        self.for_group(group).restore_volume(device, volume);
+    }
+
+    fn combine(&self, group: &str, sinks: &[String]) {
+        self.shared.combine(&self.modules, group, sinks);
+    }
+
+    fn set_default_sink(&self, name: &str) {
+        self.shared.set_default_sink(name);
+    }
+
+    fn set_subscribed(&self, subscribed: bool) {
+        self.shared.set_subscribed(subscribed);
+    }
+
+    /// Removes the combine-sinks and lets pending operations finish, then disconnects every
+    /// context and stops its mainloop
+    fn shutdown(mut self: Box<Self>) {
+        let groups: Vec<String> = self
+            .modules
+            .lock()
+            .expect("failed to lock modules")
+            .keys()
+            .cloned()
+            .collect();
+        for group in groups {
+            self.shared.combine(&self.modules, &group, &[]);
+        }
+        for volume_sync in self.isolated.values_mut() {
+            volume_sync.drain();
+            volume_sync.disconnect();
+        }
+        self.shared.drain();
+        self.shared.disconnect();
+    }
+}
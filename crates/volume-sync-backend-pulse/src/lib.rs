@@ -0,0 +1,3 @@
+pub mod contexts;
+pub mod seat;
+pub mod volume_sync;
@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+const UDEV_DATA: &str = "/run/udev/data";
+const DEFAULT_SEAT: &str = "seat0";
+/// Where logind records the state of every seat
+const SEATS: &str = "/run/systemd/seats";
+
+/// Seats of the cards seen so far by server card index, as every device info callback asks again,
+/// until the card is removed
+static SEATS_BY_CARD: Mutex<BTreeMap<u32, String>> = Mutex::new(BTreeMap::new());
+
+/// The seat a sound card is assigned to by its udev `ID_SEAT` property, given its sysfs path, e.g.
+/// `/devices/pci0000:00/0000:00:14.0/usb1/1-2/1-2:1.0/sound/card1`; cards without one belong to
+/// `seat0`. The udev database is only read the first time a card is seen, devices without a card
+/// read it every time.
+pub fn seat(card: Option<u32>, sysfs_path: &str) -> String {
+    let Some(card) = card else {
+        return read_seat(sysfs_path);
+    };
+    let mut seats = SEATS_BY_CARD.lock().expect("failed to lock seats");
+    seats
+        .entry(card)
+        .or_insert_with(|| read_seat(sysfs_path))
+        .clone()
+}
+
+/// Drops the seat of a removed card, as the server may give its index to another one
+pub fn forget(card: u32) {
+    SEATS_BY_CARD
+        .lock()
+        .expect("failed to lock seats")
+        .remove(&card);
+}
+
+fn read_seat(sysfs_path: &str) -> String {
+    let Some(card) = Path::new(sysfs_path).file_name() else {
+        return DEFAULT_SEAT.to_string();
+    };
+    let data = format!("{UDEV_DATA}/+sound:{}", card.to_string_lossy());
+    fs::read_to_string(data)
+        .ok()
+        .and_then(|data| {
+            data.lines()
+                .find_map(|line| line.strip_prefix("E:ID_SEAT="))
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| DEFAULT_SEAT.to_string())
+}
+
+/// The uid of the user whose session is in the foreground of `seat`, as recorded by logind
+fn active_uid(seat: &str) -> Result<String, String> {
+    let file = format!("{SEATS}/{seat}");
+    let data = fs::read_to_string(&file).map_err(|e| format!("failed to read {file}: {e}"))?;
+    data.lines()
+        .find_map(|line| line.strip_prefix("ACTIVE_UID="))
+        .map(str::to_string)
+        .ok_or_else(|| format!("no user is active on {seat}"))
+}
+
+/// Address of the server chosen by the `server` option: `seat:<seat>` for the server of the user
+/// active on a seat, `runtime:<dir>` for the server of an `XDG_RUNTIME_DIR`, or a server string
+/// as is
+pub fn server_address(server: &str) -> Result<String, String> {
+    if let Some(seat) = server.strip_prefix("seat:") {
+        let uid = active_uid(seat)?;
+        return Ok(format!("unix:/run/user/{uid}/pulse/native"));
+    }
+    if let Some(dir) = server.strip_prefix("runtime:") {
+        return Ok(format!("unix:{}/pulse/native", dir.trim_end_matches('/')));
+    }
+    Ok(server.to_string())
+}
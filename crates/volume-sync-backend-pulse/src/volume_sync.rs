@@ -0,0 +1,885 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+
+use pulse::callbacks::ListResult;
+use pulse::context::introspect::{CardInfo, SinkInfo, SinkInputInfo, SourceInfo, SourceOutputInfo};
+use pulse::context::subscribe::{Facility, InterestMaskSet, Operation};
+use pulse::context::{Context, ContextFlagSet, State};
+use pulse::def::{SinkFlagSet, SinkState, SourceFlagSet, SourceState, INVALID_INDEX};
+use pulse::mainloop::threaded::Mainloop;
+use pulse::proplist::Proplist;
+use pulse::volume::{ChannelVolumes, Volume};
+
+use closure::closure;
+
+use crate::seat;
+
+use volume_sync_core::config::combine_sink_name;
+use volume_sync_core::event::{VolumeSyncEvent, SHARED_CONTEXT};
+use volume_sync_core::format;
+use volume_sync_core::sink::{DeviceId, DeviceKind, SinkDetails};
+use volume_sync_core::topology::{CardDetails, StreamDetails};
+use volume_sync_core::volume::VolumeSpec;
+
+/// Property holding the sysfs path of a device's sound card
+const SYSFS_PATH: &str = "sysfs.path";
+/// Property holding the name of an ALSA device's sound card
+const CARD_NAME: &str = "alsa.card_name";
+
+fn sink_details(sink_info: &SinkInfo) -> SinkDetails {
+    let properties = sink_info
+        .proplist
+        .iter()
+        .filter_map(|key| sink_info.proplist.get_str(&key).map(|value| (key, value)))
+        .collect();
+    SinkDetails {
+        index: sink_info.index,
+        name: sink_info
+            .name
+            .as_ref()
+            .map_or_else(|| "".to_string(), |it| it.to_string()),
+        description: sink_info.description.as_ref().map(|it| it.to_string()),
+        card_name: sink_info.proplist.get_str(CARD_NAME),
+        kind: DeviceKind::Sink,
+        properties,
+        volume: sink_info.volume.max().0,
+        mute: sink_info.mute,
+        active_port: sink_info
+            .active_port
+            .as_ref()
+            .and_then(|port| port.name.as_ref())
+            .map(|name| name.to_string()),
+        ports: sink_info
+            .ports
+            .iter()
+            .filter_map(|port| port.name.as_ref().map(|name| name.to_string()))
+            .collect(),
+        card: sink_info.card,
+        suspended: sink_info.state == SinkState::Suspended,
+        sample_spec: sink_info.sample_spec.print(),
+        rate: sink_info.sample_spec.rate,
+        formats: sink_info.formats.iter().map(|f| f.print()).collect(),
+        seat: sink_info
+            .proplist
+            .get_str(SYSFS_PATH)
+            .map(|path| seat::seat(sink_info.card, &path)),
+        monitor: Some(sink_info.monitor_source).filter(|index| *index != INVALID_INDEX),
+        flat_volume: sink_info.flags.contains(SinkFlagSet::FLAT_VOLUME),
+        hw_volume: sink_info.flags.contains(SinkFlagSet::HW_VOLUME_CTRL),
+        volume_steps: sink_info.n_volume_steps,
+    }
+}
+
+fn source_details(source_info: &SourceInfo) -> SinkDetails {
+    let properties = source_info
+        .proplist
+        .iter()
+        .filter_map(|key| source_info.proplist.get_str(&key).map(|value| (key, value)))
+        .collect();
+    SinkDetails {
+        index: source_info.index,
+        name: source_info
+            .name
+            .as_ref()
+            .map_or_else(|| "".to_string(), |it| it.to_string()),
+        description: source_info.description.as_ref().map(|it| it.to_string()),
+        card_name: source_info.proplist.get_str(CARD_NAME),
+        kind: DeviceKind::Source,
+        properties,
+        volume: source_info.volume.max().0,
+        mute: source_info.mute,
+        active_port: source_info
+            .active_port
+            .as_ref()
+            .and_then(|port| port.name.as_ref())
+            .map(|name| name.to_string()),
+        ports: source_info
+            .ports
+            .iter()
+            .filter_map(|port| port.name.as_ref().map(|name| name.to_string()))
+            .collect(),
+        card: source_info.card,
+        suspended: source_info.state == SourceState::Suspended,
+        sample_spec: source_info.sample_spec.print(),
+        rate: source_info.sample_spec.rate,
+        formats: source_info.formats.iter().map(|f| f.print()).collect(),
+        seat: source_info
+            .proplist
+            .get_str(SYSFS_PATH)
+            .map(|path| seat::seat(source_info.card, &path)),
+        monitor: None,
+        flat_volume: false,
+        hw_volume: source_info.flags.contains(SourceFlagSet::HW_VOLUME_CTRL),
+        volume_steps: source_info.n_volume_steps,
+    }
+}
+
+fn card_details(card_info: &CardInfo) -> CardDetails {
+    CardDetails {
+        index: card_info.index,
+        name: card_info
+            .name
+            .as_ref()
+            .map_or_else(|| "".to_string(), |it| it.to_string()),
+        active_profile: card_info
+            .active_profile
+            .as_ref()
+            .and_then(|p| p.name.as_ref())
+            .map(|name| name.to_string()),
+        profiles: card_info
+            .profiles
+            .iter()
+            .filter_map(|p| p.name.as_ref().map(|name| name.to_string()))
+            .collect(),
+    }
+}
+
+/// A recording stream as a device named after its application
+fn recording_details(source_output_info: &SourceOutputInfo) -> SinkDetails {
+    let proplist = &source_output_info.proplist;
+    let properties = proplist
+        .iter()
+        .filter_map(|key| proplist.get_str(&key).map(|value| (key, value)))
+        .collect();
+    let name = proplist
+        .get_str(pulse::proplist::properties::APPLICATION_NAME)
+        .or_else(|| {
+            source_output_info
+                .name
+                .as_ref()
+                .map(|name| name.to_string())
+        })
+        .unwrap_or_default();
+    SinkDetails {
+        index: source_output_info.index,
+        name,
+        description: source_output_info.name.as_ref().map(|it| it.to_string()),
+        card_name: None,
+        kind: DeviceKind::Recording,
+        properties,
+        volume: source_output_info.volume.max().0,
+        mute: source_output_info.mute,
+        active_port: None,
+        ports: Vec::new(),
+        card: None,
+        suspended: false,
+        sample_spec: source_output_info.sample_spec.print(),
+        rate: source_output_info.sample_spec.rate,
+        formats: Vec::new(),
+        seat: None,
+        monitor: None,
+        flat_volume: false,
+        hw_volume: false,
+        volume_steps: 0,
+    }
+}
+
+fn stream_details(sink_input_info: &SinkInputInfo) -> StreamDetails {
+    let name = sink_input_info
+        .proplist
+        .get_str(pulse::proplist::properties::APPLICATION_NAME)
+        .or_else(|| sink_input_info.name.as_ref().map(|name| name.to_string()))
+        .unwrap_or_default();
+    StreamDetails {
+        index: sink_input_info.index,
+        name,
+        binary: sink_input_info
+            .proplist
+            .get_str(pulse::proplist::properties::APPLICATION_PROCESS_BINARY),
+        sink: sink_input_info.sink,
+        volume: sink_input_info.volume.max().0,
+        corked: sink_input_info.corked,
+    }
+}
+
+/// Channel volumes of every sink and source seen in events, listings and writes, shared by every
+/// context so syncing doesn't need a round-trip to read the volume it copies
+pub type VolumeCache = Arc<Mutex<BTreeMap<DeviceId, ChannelVolumes>>>;
+/// Index of the module loaded for every group
+pub type Modules = Arc<Mutex<BTreeMap<String, u32>>>;
+
+fn cache_volume(cache: &VolumeCache, device: DeviceId, volume: &ChannelVolumes) {
+    cache
+        .lock()
+        .expect("failed to lock volume cache")
+        .insert(device, *volume);
+}
+
+/// Looks up a sink or source, caches its volume and hands its details to `f` on the mainloop thread
+fn fetch_details<F>(context: &Rc<RefCell<Context>>, cache: &VolumeCache, device: DeviceId, mut f: F)
+where
+    F: FnMut(SinkDetails) + 'static,
+{
+    let introspect = context.borrow_mut().introspect();
+    match device.kind {
+        DeviceKind::Sink => {
+            introspect.get_sink_info_by_index(
+                device.index,
+                closure!(clone cache, |result| {
+                    if let ListResult::Item(sink_info) = result {
+                        cache_volume(&cache, device, &sink_info.volume);
+                        f(sink_details(sink_info));
+                    }
+                }),
+            );
+        }
+        DeviceKind::Source => {
+            introspect.get_source_info_by_index(
+                device.index,
+                closure!(clone cache, |result| {
+                    if let ListResult::Item(source_info) = result {
+                        cache_volume(&cache, device, &source_info.volume);
+                        f(source_details(source_info));
+                    }
+                }),
+            );
+        }
+        DeviceKind::Recording => {
+            introspect.get_source_output_info(
+                device.index,
+                closure!(clone cache, |result| {
+                    if let ListResult::Item(source_output_info) = result {
+                        cache_volume(&cache, device, &source_output_info.volume);
+                        f(recording_details(source_output_info));
+                    }
+                }),
+            );
+        }
+    }
+}
+
+/// Hands the channel volumes of a sink or source to `f`, right away when cached and otherwise on
+/// the mainloop thread once the server answers
+fn read_volume<F>(context: &Rc<RefCell<Context>>, cache: &VolumeCache, device: DeviceId, mut f: F)
+where
+    F: FnMut(&ChannelVolumes) + 'static,
+{
+    let cached = cache
+        .lock()
+        .expect("failed to lock volume cache")
+        .get(&device)
+        .copied();
+    if let Some(volume) = cached {
+        f(&volume);
+        return;
+    }
+
+    tracing::debug!("volume of {device} not cached, asking the server");
+    let introspect = context.borrow_mut().introspect();
+    match device.kind {
+        DeviceKind::Sink => {
+            introspect.get_sink_info_by_index(
+                device.index,
+                closure!(clone cache, |result| {
+                    if let ListResult::Item(sink_info) = result {
+                        cache_volume(&cache, device, &sink_info.volume);
+                        f(&sink_info.volume);
+                    }
+                }),
+            );
+        }
+        DeviceKind::Source => {
+            introspect.get_source_info_by_index(
+                device.index,
+                closure!(clone cache, |result| {
+                    if let ListResult::Item(source_info) = result {
+                        cache_volume(&cache, device, &source_info.volume);
+                        f(&source_info.volume);
+                    }
+                }),
+            );
+        }
+        DeviceKind::Recording => {
+            introspect.get_source_output_info(
+                device.index,
+                closure!(clone cache, |result| {
+                    if let ListResult::Item(source_output_info) = result {
+                        cache_volume(&cache, device, &source_output_info.volume);
+                        f(&source_output_info.volume);
+                    }
+                }),
+            );
+        }
+    }
+}
+
+fn write_volume(
+    context: &Rc<RefCell<Context>>,
+    cache: &VolumeCache,
+    device: DeviceId,
+    volume: &ChannelVolumes,
+) {
+    cache_volume(cache, device, volume);
+    let mut introspect = context.borrow_mut().introspect();
+    match device.kind {
+        DeviceKind::Sink => {
+            introspect.set_sink_volume_by_index(device.index, volume, None);
+        }
+        DeviceKind::Source => {
+            introspect.set_source_volume_by_index(device.index, volume, None);
+        }
+        DeviceKind::Recording => {
+            introspect.set_source_output_volume(device.index, volume, None);
+        }
+    }
+}
+
+/// State of a context from one of its own callbacks, which run on the mainloop thread while the
+/// mainloop lock keeps every other user of the context out
+fn context_state(context: &Rc<RefCell<Context>>) -> Option<State> {
+    match context.try_borrow() {
+        Ok(context) => Some(context.get_state()),
+        Err(_) => {
+            tracing::warn!("context busy in state callback, skipping state change");
+            None
+        }
+    }
+}
+
+/// Forwards a subscribe event for a sink or source to the event loop
+fn device_event(
+    context: &Rc<RefCell<Context>>,
+    cache: &VolumeCache,
+    sender: &Sender<VolumeSyncEvent>,
+    device: DeviceId,
+    op: Operation,
+) {
+    match op {
+        Operation::New => {
+            tracing::debug!(%device, "new device");
+            fetch_details(
+                context,
+                cache,
+                device,
+                closure!(clone sender, |details| {
+                    if details.name.is_empty() {
+                        return;
+                    }
+                    let event = match details.kind {
+                        DeviceKind::Sink => VolumeSyncEvent::SinkNew(details),
+                        DeviceKind::Source => VolumeSyncEvent::SourceNew(details),
+                        DeviceKind::Recording => VolumeSyncEvent::RecordingNew(details),
+                    };
+                    sender.send(event).expect("failed to send");
+                }),
+            );
+        }
+        Operation::Changed => {
+            tracing::info!(%device, "device changed");
+            fetch_details(
+                context,
+                cache,
+                device,
+                closure!(clone sender, |details| {
+                    let event = match details.kind {
+                        DeviceKind::Sink => VolumeSyncEvent::SinkChanged(details),
+                        DeviceKind::Source => VolumeSyncEvent::SourceChanged(details),
+                        DeviceKind::Recording => VolumeSyncEvent::RecordingChanged(details),
+                    };
+                    sender.send(event).expect("failed to send changed event");
+                }),
+            );
+        }
+        Operation::Removed => {
+            tracing::debug!(%device, "device removed");
+            cache
+                .lock()
+                .expect("failed to lock volume cache")
+                .remove(&device);
+            let event = match device.kind {
+                DeviceKind::Sink => VolumeSyncEvent::SinkRemoved(device.index),
+                DeviceKind::Source => VolumeSyncEvent::SourceRemoved(device.index),
+                DeviceKind::Recording => VolumeSyncEvent::RecordingRemoved(device.index),
+            };
+            sender.send(event).expect("failed to send removed event");
+        }
+    }
+}
+
+const SUBSCRIPTIONS: InterestMaskSet = InterestMaskSet::SINK
+    .union(InterestMaskSet::SOURCE)
+    .union(InterestMaskSet::CARD)
+    .union(InterestMaskSet::SINK_INPUT)
+    .union(InterestMaskSet::SOURCE_OUTPUT);
+
+pub struct VolumeSync {
+    pub mainloop: Rc<RefCell<Mainloop>>,
+    pub context: Rc<RefCell<Context>>,
+    pub sender: Sender<VolumeSyncEvent>,
+    pub cache: VolumeCache,
+    /// The group owning an isolated context, which only writes volumes and never subscribes
+    pub group: Option<String>,
+}
+
+impl VolumeSync {
+    /// Copies the volume of `from` to `to`, unless they differ by less than `min_delta`
+    pub fn sync_volume(&self, from: DeviceId, to: DeviceId, min_delta: u32) {
+        self.sync_volume_to_many(from, &[to], min_delta);
+    }
+
+    /// Reads the volume of `from` once and copies it to every target that differs by at least
+    /// `min_delta`
+    pub fn sync_volume_to_many(&self, from: DeviceId, targets: &[DeviceId], min_delta: u32) {
+        let targets: Vec<DeviceId> = targets.iter().copied().filter(|to| *to != from).collect();
+        if targets.is_empty() {
+            return;
+        }
+
+        tracing::debug!(%from, to = ?targets, "syncing volume");
+        self.mainloop.borrow_mut().lock();
+        read_volume(
+            &self.context,
+            &self.cache,
+            from,
+            closure!(clone self.context, clone self.cache, |volume| {
+                for &to in &targets {
+                    if min_delta == 0 {
+                        write_volume(&context, &cache, to, volume);
+                        continue;
+                    }
+                    let volume = *volume;
+                    read_volume(&context, &cache, to, closure!(clone context, clone cache, |current| {
+                        let delta = volume.max().0.abs_diff(current.max().0);
+                        if delta < min_delta {
+                            tracing::debug!(%to, delta, min_delta, "skipping sync below minimum difference");
+                            return;
+                        }
+                        write_volume(&context, &cache, to, &volume);
+                    }));
+                }
+            }),
+        );
+        self.mainloop.borrow_mut().unlock();
+    }
+
+    pub fn set_volume(&self, devices: &[DeviceId], volume: VolumeSpec) {
+        tracing::info!(%volume, ?devices, "setting volume");
+        self.update_volume(devices, move |current| volume.apply(current));
+    }
+
+    pub fn restore_volume(&self, device: DeviceId, volume: u32) {
+        tracing::info!(%device, volume = %format::volume(volume), "restoring volume");
+        self.update_volume(&[device], move |_| volume);
+    }
+
+    /// Computes a new level from the first device's current one and scales every device's own
+    /// channels to it, keeping the balance of each
+    fn update_volume<F>(&self, devices: &[DeviceId], update: F)
+    where
+        F: Fn(u32) -> u32 + 'static,
+    {
+        let Some(&first) = devices.first() else {
+            return;
+        };
+
+        let devices = devices.to_vec();
+        self.mainloop.borrow_mut().lock();
+        read_volume(
+            &self.context,
+            &self.cache,
+            first,
+            closure!(clone self.context, clone self.cache, |volume| {
+                let level = Volume(update(volume.max().0));
+                for &device in &devices {
+                    read_volume(&context, &cache, device, closure!(clone context, clone cache, |current| {
+                        let mut new_volume = *current;
+                        new_volume.scale(level);
+                        write_volume(&context, &cache, device, &new_volume);
+                    }));
+                }
+            }),
+        );
+        self.mainloop.borrow_mut().unlock();
+    }
+
+    pub fn new(
+        mainloop: Rc<RefCell<Mainloop>>,
+        sender: Sender<VolumeSyncEvent>,
+        cache: VolumeCache,
+        group: Option<String>,
+        server: Option<&str>,
+    ) -> VolumeSync {
+        let mut proplist = Proplist::new().unwrap();
+        proplist
+            .set_str(pulse::proplist::properties::APPLICATION_NAME, "volume-sync")
+            .unwrap();
+        let context = Rc::new(RefCell::new(
+            Context::new_with_proplist(mainloop.borrow().deref(), "volume-sync", &proplist)
+                .expect("failed to create context"),
+        ));
+
+        tracing::info!("connecting context");
+        context
+            .borrow_mut()
+            .connect(server, ContextFlagSet::NOFLAGS, None)
+            .expect("failed to connect context");
+
+        return VolumeSync {
+            mainloop,
+            context,
+            sender,
+            cache,
+            group,
+        };
+    }
+
+    pub fn context_name(&self) -> String {
+        self.group
+            .clone()
+            .unwrap_or_else(|| SHARED_CONTEXT.to_string())
+    }
+
+    /// Waits until every operation already sent to the server has completed
+    pub fn drain(&self) {
+        let (done, drained) = channel();
+        self.mainloop.borrow_mut().lock();
+        let operation = self.context.borrow_mut().drain(move || {
+            let _ = done.send(());
+        });
+        self.mainloop.borrow_mut().unlock();
+        if operation.is_some() {
+            tracing::info!(
+                "waiting for pending operations on context {}",
+                self.context_name()
+            );
+            let _ = drained.recv();
+        }
+    }
+
+    pub fn disconnect(&mut self) {
+        tracing::info!("disconnecting context {}", self.context_name());
+        self.mainloop.borrow_mut().lock();
+        self.context.borrow_mut().set_state_callback(None);
+        self.context.borrow_mut().disconnect();
+        self.mainloop.borrow_mut().unlock();
+        self.mainloop.borrow_mut().stop();
+    }
+
+    /// Waits for the context to become ready, taking every state change from a callback so the
+    /// mainloop lock is never held while waiting
+    fn wait_ready(&self) -> Result<(), &'static str> {
+        let (states, received) = channel();
+        self.mainloop.borrow_mut().lock();
+        tracing::debug!("setting state callback");
+        self.context
+            .borrow_mut()
+            .set_state_callback(Some(Box::new(closure!(
+                clone self.context,
+                || {
+                    tracing::debug!("got state callback");
+                    if let Some(state) = context_state(&context) {
+                        let _ = states.send(state);
+                    }
+                }
+            ))));
+        let mut state = self.context.borrow().get_state();
+        self.mainloop.borrow_mut().unlock();
+
+        loop {
+            match state {
+                State::Ready => return Ok(()),
+                State::Failed | State::Terminated => {
+                    tracing::error!("context state failed/terminated, quitting...");
+                    self.mainloop.borrow_mut().stop();
+                    return Err("failed to get ready context");
+                }
+                _ => {
+                    state = received
+                        .recv()
+                        .map_err(|_| "context state callback dropped")?;
+                }
+            }
+        }
+    }
+
+    pub fn connect(&mut self) -> Result<(), &'static str> {
+        let _span = tracing::info_span!("connect", context = %self.context_name()).entered();
+        self.wait_ready()?;
+        self.mainloop.borrow_mut().lock();
+
+        tracing::debug!("setting state report callback");
+        let name = self.context_name();
+        self.context
+            .borrow_mut()
+            .set_state_callback(Some(Box::new(closure!(
+                clone self.sender,
+                clone self.context,
+                clone name,
+                || {
+                    if let Some(state) = context_state(&context) {
+                        tracing::info!(context = %name, ?state, "context state changed");
+                        let _ = sender.send(VolumeSyncEvent::ContextState(name.clone(), format!("{state:?}")));
+                    }
+                }
+            ))));
+        self.sender
+            .send(VolumeSyncEvent::ContextState(
+                name,
+                format!("{:?}", State::Ready),
+            ))
+            .expect("failed to send context state");
+
+        if self.group.is_some() {
+            self.mainloop.borrow_mut().unlock();
+            return Ok(());
+        }
+
+        tracing::debug!("setting subscribe callback");
+        self.context.borrow_mut().set_subscribe_callback(Some(Box::new(closure!(
+            clone self.sender,
+            clone self.context,
+            clone self.cache,
+            |facility, op, index| {
+                tracing::debug!("got subscribe callback");
+                match (facility, op) {
+                    (Some(Facility::Sink), Some(op)) => {
+                        device_event(&context, &cache, &sender, DeviceId::sink(index), op);
+                    }
+                    (Some(Facility::Source), Some(op)) => {
+                        device_event(&context, &cache, &sender, DeviceId::source(index), op);
+                    }
+                    (Some(Facility::SourceOutput), Some(op)) => {
+                        device_event(&context, &cache, &sender, DeviceId::recording(index), op);
+                    }
+                    (Some(Facility::Card), Some(Operation::Removed)) => {
+                        seat::forget(index);
+                        sender.send(VolumeSyncEvent::CardRemoved(index)).expect("failed to send card event");
+                    }
+                    (Some(Facility::Card), Some(_)) => {
+                        context.borrow_mut().introspect().get_card_info_by_index(index, closure!(
+                            clone sender,
+                            |result| {
+                                if let ListResult::Item(card_info) = result {
+                                    sender
+                                        .send(VolumeSyncEvent::CardChanged(card_details(card_info)))
+                                        .expect("failed to send card event");
+                                }
+                            }
+                        ));
+                    }
+                    (Some(Facility::SinkInput), Some(Operation::Removed)) => {
+                        sender.send(VolumeSyncEvent::StreamRemoved(index)).expect("failed to send stream event");
+                    }
+                    (Some(Facility::SinkInput), Some(_)) => {
+                        context.borrow_mut().introspect().get_sink_input_info(index, closure!(
+                            clone sender,
+                            |result| {
+                                if let ListResult::Item(sink_input_info) = result {
+                                    sender
+                                        .send(VolumeSyncEvent::StreamChanged(stream_details(sink_input_info)))
+                                        .expect("failed to send stream event");
+                                }
+                            }
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        ))));
+
+        tracing::info!("subscribing to sink, source, card, stream and recording events");
+        self.subscribe(SUBSCRIPTIONS);
+
+        self.mainloop.borrow_mut().unlock();
+
+        Ok(())
+    }
+
+    fn subscribe(&self, mask: InterestMaskSet) {
+        self.context.borrow_mut().subscribe(mask, |success| {
+            tracing::debug!("got subscribe context");
+            if !success {
+                panic!("failed to subscribe context");
+            }
+        });
+    }
+
+    /// Unloads the combine-sink of `group` and loads one over `slaves` in its place, unless there
+    /// are none
+    pub fn combine(&self, modules: &Modules, group: &str, slaves: &[String]) {
+        self.mainloop.borrow_mut().lock();
+        let mut introspect = self.context.borrow_mut().introspect();
+        let previous = modules
+            .lock()
+            .expect("failed to lock modules")
+            .remove(group);
+        if let Some(index) = previous {
+            let group = group.to_string();
+            introspect.unload_module(index, move |success| {
+                if !success {
+                    tracing::warn!("{group}: failed to unload combine-sink module {index}");
+                }
+            });
+        }
+        if !slaves.is_empty() {
+            let argument = format!(
+                "sink_name={} slaves={} sink_properties='device.description=\"volume-sync {group}\"'",
+                combine_sink_name(group),
+                slaves.join(",")
+            );
+            let (modules, group) = (modules.clone(), group.to_string());
+            introspect.load_module("module-combine-sink", &argument, move |index| {
+                if index == INVALID_INDEX {
+                    tracing::warn!("{group}: failed to load combine-sink");
+                    return;
+                }
+                modules
+                    .lock()
+                    .expect("failed to lock modules")
+                    .insert(group.clone(), index);
+            });
+        }
+        self.mainloop.borrow_mut().unlock();
+    }
+
+    pub fn set_default_sink(&self, name: &str) {
+        self.mainloop.borrow_mut().lock();
+        let sink = name.to_string();
+        self.context
+            .borrow_mut()
+            .set_default_sink(name, move |success| {
+                if !success {
+                    tracing::warn!("failed to make {sink} the default sink");
+                }
+            });
+        self.mainloop.borrow_mut().unlock();
+    }
+
+    /// Stops or restarts receiving server events, while idle nothing is worth waking up for
+    pub fn set_subscribed(&self, subscribed: bool) {
+        if subscribed {
+            tracing::info!("resubscribing to sink, source, card, stream and recording events");
+        } else {
+            tracing::info!("unsubscribing from all events");
+        }
+        self.mainloop.borrow_mut().lock();
+        self.subscribe(if subscribed {
+            SUBSCRIPTIONS
+        } else {
+            InterestMaskSet::NULL
+        });
+        self.mainloop.borrow_mut().unlock();
+    }
+
+    /// Lists every card without blocking, sending them all as `CardListLoaded` once the list ends
+    pub fn request_cards(&self) {
+        self.mainloop.borrow_mut().lock();
+        tracing::debug!("get_card_info_list");
+        let mut items = Vec::new();
+        self.context
+            .borrow_mut()
+            .introspect()
+            .get_card_info_list(closure!(
+                clone self.sender,
+                |result| match result {
+                    ListResult::Item(info) => items.push(card_details(info)),
+                    ListResult::End => sender
+                        .send(VolumeSyncEvent::CardListLoaded(std::mem::take(&mut items)))
+                        .expect("failed to send card list"),
+                    ListResult::Error => tracing::warn!("failed to list cards"),
+                }
+            ));
+        self.mainloop.borrow_mut().unlock();
+    }
+
+    /// Lists every sink without blocking, sending them all as `SinkListLoaded` once the list ends
+    pub fn request_sinks(&self) {
+        self.mainloop.borrow_mut().lock();
+        tracing::debug!("get_sink_info_list");
+        let mut items = Vec::new();
+        self.context
+            .borrow_mut()
+            .introspect()
+            .get_sink_info_list(closure!(
+                clone self.sender,
+                clone self.cache,
+                |result| match result {
+                    ListResult::Item(info) => {
+                        cache_volume(&cache, DeviceId::sink(info.index), &info.volume);
+                        items.push(sink_details(info));
+                    }
+                    ListResult::End => sender
+                        .send(VolumeSyncEvent::SinkListLoaded(std::mem::take(&mut items)))
+                        .expect("failed to send sink list"),
+                    ListResult::Error => tracing::warn!("failed to list sinks"),
+                }
+            ));
+        self.mainloop.borrow_mut().unlock();
+    }
+
+    /// Lists every source without blocking, sending them all as `SourceListLoaded` once the list ends
+    pub fn request_sources(&self) {
+        self.mainloop.borrow_mut().lock();
+        tracing::debug!("get_source_info_list");
+        let mut items = Vec::new();
+        self.context
+            .borrow_mut()
+            .introspect()
+            .get_source_info_list(closure!(
+                clone self.sender,
+                clone self.cache,
+                |result| match result {
+                    ListResult::Item(info) => {
+                        cache_volume(&cache, DeviceId::source(info.index), &info.volume);
+                        items.push(source_details(info));
+                    }
+                    ListResult::End => sender
+                        .send(VolumeSyncEvent::SourceListLoaded(std::mem::take(&mut items)))
+                        .expect("failed to send source list"),
+                    ListResult::Error => tracing::warn!("failed to list sources"),
+                }
+            ));
+        self.mainloop.borrow_mut().unlock();
+    }
+
+    /// Lists every recording stream without blocking, sending them all as `RecordingListLoaded`
+    /// once the list ends
+    pub fn request_recordings(&self) {
+        self.mainloop.borrow_mut().lock();
+        tracing::debug!("get_source_output_info_list");
+        let mut items = Vec::new();
+        self.context
+            .borrow_mut()
+            .introspect()
+            .get_source_output_info_list(closure!(
+                clone self.sender,
+                clone self.cache,
+                |result| match result {
+                    ListResult::Item(info) => {
+                        cache_volume(&cache, DeviceId::recording(info.index), &info.volume);
+                        items.push(recording_details(info));
+                    }
+                    ListResult::End => sender
+                        .send(VolumeSyncEvent::RecordingListLoaded(std::mem::take(&mut items)))
+                        .expect("failed to send recording list"),
+                    ListResult::Error => tracing::warn!("failed to list recordings"),
+                }
+            ));
+        self.mainloop.borrow_mut().unlock();
+    }
+
+    /// Lists every stream without blocking, sending them all as `StreamListLoaded` once the list ends
+    pub fn request_streams(&self) {
+        self.mainloop.borrow_mut().lock();
+        tracing::debug!("get_sink_input_info_list");
+        let mut items = Vec::new();
+        self.context
+            .borrow_mut()
+            .introspect()
+            .get_sink_input_info_list(closure!(
+                clone self.sender,
+                |result| match result {
+                    ListResult::Item(info) => items.push(stream_details(info)),
+                    ListResult::End => sender
+                        .send(VolumeSyncEvent::StreamListLoaded(std::mem::take(&mut items)))
+                        .expect("failed to send stream list"),
+                    ListResult::Error => tracing::warn!("failed to list streams"),
+                }
+            ));
+        self.mainloop.borrow_mut().unlock();
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+
+use crate::config::GroupConfig;
+use crate::sink::DeviceId;
+use crate::volume::VolumeSpec;
+
+/// A sound server or mixer whose volumes are kept in sync. Implementations report what they see
+/// as `VolumeSyncEvent`s and carry out the engine's actions; every call returns without waiting
+/// for the device.
+pub trait Backend {
+    /// Adjusts to the groups of a freshly loaded config, e.g. opening connections for isolated
+    /// groups
+    fn configure(&mut self, groups: &BTreeMap<String, GroupConfig>);
+
+    /// Lists every card, sink, source, recording and stream, delivered as `*ListLoaded` events
+    /// with streams last
+    fn request_topology(&self);
+
+    /// Copies the volume of `from` to every device in `to` that differs by at least `min_delta`
+    fn sync_volume(&self, group: &str, from: DeviceId, to: &[DeviceId], min_delta: u32);
+
+    /// Applies `volume` to the current level of the first device and sets every device to it
+    fn set_volume(&self, group: &str, devices: &[DeviceId], volume: VolumeSpec);
+
+    fn restore_volume(&self, group: &str, device: DeviceId, volume: u32);
+
+    /// Keeps a combine-sink for `group` over the sinks named `sinks`, replacing the previous one,
+    /// or removes it when `sinks` is empty
+    fn combine(&self, group: &str, sinks: &[String]) {
+        if !sinks.is_empty() {
+            tracing::warn!("{group}: this backend can't combine sinks");
+        }
+    }
+
+    /// Makes the sink named `name` the default one
+    fn set_default_sink(&self, name: &str) {
+        tracing::warn!("this backend has no default sink to set to {name}");
+    }
+
+    /// Starts or stops reporting device changes
+    fn set_subscribed(&self, subscribed: bool);
+
+    /// Lets pending writes finish and disconnects
+    fn shutdown(self: Box<Self>);
+}
@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+
+use crate::graph::GraphFormat;
+use crate::sink::DeviceKind;
+use crate::volume::{parse_percent, VolumeSpec};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Command {
+    SetVolume {
+        group: String,
+        volume: VolumeSpec,
+    },
+    Status,
+    /// Versioned state of one group or every group as JSON
+    Snapshot(Option<String>),
+    ListSinks,
+    /// Check the setup for common problems
+    Doctor,
+    SaveProfile(String),
+    LoadProfile(String),
+    ListProfiles,
+    /// List the devices whose name matches a pattern, optionally only those of one kind and as
+    /// JSON
+    Resolve {
+        pattern: String,
+        #[serde(default)]
+        kind: Option<DeviceKind>,
+        json: bool,
+    },
+    /// Take a pause token on a group, optionally leased for some seconds
+    Pause {
+        group: String,
+        owner: String,
+        lease: Option<u64>,
+    },
+    /// Release a pause token, resuming the group once every token is gone
+    Resume {
+        group: String,
+        owner: String,
+    },
+    /// Change the log level until the config is next loaded, as a level or filter directives
+    SetLogLevel(String),
+    /// Export the groups and their members as a graph
+    Dump(GraphFormat),
+    /// Run every step of a macro from the config
+    Run(String),
+    /// Set the members of every group that isn't paused back to the group level
+    Resync,
+    /// Recent volume changes and what triggered them, optionally as JSON
+    History {
+        json: bool,
+    },
+    /// Record the current offset of every member of a relative group from its level
+    Calibrate(String),
+    /// Show how a group level in percent is translated for a member
+    Explain {
+        group: String,
+        device: String,
+        volume: u32,
+    },
+}
+
+pub type Reply = Result<String, String>;
+
+/// Longest command line accepted, in bytes
+pub const MAX_COMMAND_LEN: usize = 4096;
+/// Most words accepted in a command line
+const MAX_ARGS: usize = 16;
+
+/// Parses a number of seconds, digits only
+fn parse_seconds(s: &str) -> Result<u64, String> {
+    s.bytes()
+        .all(|b| b.is_ascii_digit())
+        .then(|| s.parse().ok())
+        .flatten()
+        .ok_or_else(|| format!("invalid lease: {s}"))
+}
+
+/// Rejects a command, or an argument taken from elsewhere, that is too long or contains control
+/// characters
+pub fn validate(command: &str) -> Result<(), String> {
+    if command.len() > MAX_COMMAND_LEN {
+        return Err(format!("command longer than {MAX_COMMAND_LEN} bytes"));
+    }
+    if command.chars().any(char::is_control) {
+        return Err("command contains control characters".to_string());
+    }
+    Ok(())
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        validate(line.trim_end())?;
+        let args: Vec<&str> = line.split_whitespace().collect();
+        if args.len() > MAX_ARGS {
+            return Err(format!("command has more than {MAX_ARGS} words"));
+        }
+        match args.as_slice() {
+            ["set", group, volume] => Ok(Command::SetVolume {
+                group: group.to_string(),
+                volume: volume.parse()?,
+            }),
+            ["status"] => Ok(Command::Status),
+            ["snapshot"] => Ok(Command::Snapshot(None)),
+            ["snapshot", group] => Ok(Command::Snapshot(Some(group.to_string()))),
+            ["list-sinks"] => Ok(Command::ListSinks),
+            ["doctor"] => Ok(Command::Doctor),
+            ["profile", "save", name] => Ok(Command::SaveProfile(name.to_string())),
+            ["profile", "load", name] => Ok(Command::LoadProfile(name.to_string())),
+            ["profile", "list"] => Ok(Command::ListProfiles),
+            ["run", name] => Ok(Command::Run(name.to_string())),
+            ["resync"] => Ok(Command::Resync),
+            ["history"] => Ok(Command::History { json: false }),
+            ["history", "--json"] => Ok(Command::History { json: true }),
+            ["calibrate", group] => Ok(Command::Calibrate(group.to_string())),
+            ["log-level", level] => Ok(Command::SetLogLevel(level.to_string())),
+            ["dump", "--format", format] => Ok(Command::Dump(format.parse()?)),
+            ["pause", group, owner] => Ok(Command::Pause {
+                group: group.to_string(),
+                owner: owner.to_string(),
+                lease: None,
+            }),
+            ["pause", group, owner, seconds] => Ok(Command::Pause {
+                group: group.to_string(),
+                owner: owner.to_string(),
+                lease: Some(parse_seconds(seconds)?),
+            }),
+            ["resume", group, owner] => Ok(Command::Resume {
+                group: group.to_string(),
+                owner: owner.to_string(),
+            }),
+            ["resolve", pattern] => Ok(Command::Resolve {
+                pattern: pattern.to_string(),
+                kind: None,
+                json: false,
+            }),
+            ["resolve", pattern, "--json"] => Ok(Command::Resolve {
+                pattern: pattern.to_string(),
+                kind: None,
+                json: true,
+            }),
+            ["explain", group, device, volume] => Ok(Command::Explain {
+                group: group.to_string(),
+                device: device.to_string(),
+                volume: parse_percent(volume)?,
+            }),
+            [] => Err("empty command".to_string()),
+            _ => Err(format!("unknown command: {line}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let long = format!("set desk {}", "1".repeat(MAX_COMMAND_LEN));
+        let many = vec!["x"; MAX_ARGS + 1].join(" ");
+        let cases = [
+            (long.as_str(), "command longer than 4096 bytes"),
+            (many.as_str(), "command has more than 16 words"),
+            ("set desk\n40", "command contains control characters"),
+            ("set de\x1bsk 40", "command contains control characters"),
+            ("set\tdesk 40", "command contains control characters"),
+            ("", "empty command"),
+            ("   ", "empty command"),
+            ("pause desk me -5", "invalid lease: -5"),
+            ("pause desk me +5", "invalid lease: +5"),
+            ("set desk", "unknown command: set desk"),
+        ];
+        for (line, error) in cases {
+            assert_eq!(
+                Command::parse(line).err().as_deref(),
+                Some(error),
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn accepts_lines_at_the_limits() {
+        let padded = format!("status{}", " ".repeat(MAX_COMMAND_LEN - "status".len()));
+        let words = format!("resolve {}", vec!["x"; MAX_ARGS - 1].join(" "));
+        let cases = [
+            ("status\n", true),
+            ("status\r\n", true),
+            (padded.as_str(), true),
+            (words.as_str(), false),
+            ("pause desk me 30", true),
+        ];
+        for (line, valid) in cases {
+            let result = Command::parse(line);
+            if valid {
+                assert!(result.is_ok(), "{line:?}: {result:?}");
+            } else {
+                assert!(
+                    result
+                        .as_ref()
+                        .is_err_and(|e| e.starts_with("unknown command")),
+                    "{line:?}: {result:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,1049 @@
+use std::collections::BTreeMap;
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::{env, fs};
+
+use serde::{Deserialize, Serialize};
+
+use crate::format::Reference;
+use crate::matcher;
+use crate::sink::{DeviceKind, SinkDetails};
+use crate::topology::StreamDetails;
+use crate::translate::{default_stages, Stage, Translation};
+use crate::volume::{Delta, VOLUME_NORM};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum LogLevel {
+    #[serde(alias = "off")]
+    Off,
+    #[serde(alias = "error")]
+    Error,
+    #[serde(alias = "warn")]
+    Warn,
+    #[serde(alias = "info")]
+    Info,
+    #[serde(alias = "debug")]
+    Debug,
+    #[serde(alias = "trace")]
+    Trace,
+}
+
+/// One log level for everything, or levels by module and by group over a default one
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum LogFilter {
+    Level(LogLevel),
+    Table {
+        default: Option<LogLevel>,
+        /// Levels for everything logged while handling a group
+        #[serde(default)]
+        group: BTreeMap<String, LogLevel>,
+        /// Levels by module path prefix, e.g. `volume_sync_backend_pulse`
+        #[serde(flatten)]
+        modules: BTreeMap<String, LogLevel>,
+    },
+}
+
+/// When the log file is moved aside for a new one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Never,
+    /// Once it grows past `log_max_size`
+    #[default]
+    Size,
+    Daily,
+}
+
+/// Sound system whose volumes are synced
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// PulseAudio, or PipeWire through pipewire-pulse
+    #[default]
+    Pulse,
+    /// Mixer elements of bare ALSA cards
+    Alsa,
+    /// Gain stages for JACK clients
+    Jack,
+}
+
+/// How the group level follows a change of one member
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    /// Copy the changed member's volume to the others
+    #[default]
+    Copy,
+    /// Set every member to the loudest one
+    Highest,
+    /// Set every member to the quietest one
+    Lowest,
+    /// Set every member to the average of all members
+    Average,
+}
+
+/// How member volumes relate to the group level
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncMode {
+    /// Members are set to the group level
+    #[default]
+    Absolute,
+    /// Members keep the offset from the group level they had when calibrated
+    Relative,
+}
+
+/// Which change wins when several members change within the arbitration window
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Arbitration {
+    /// The last change received
+    #[default]
+    LastWriter,
+    /// The loudest member
+    Highest,
+    /// The member matching the earliest pattern in `priority`
+    Priority,
+}
+
+/// Which way volume changes flow through a group member
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Changes are copied from and to the member
+    #[default]
+    Both,
+    /// Changes are copied from the member but never to it
+    Lead,
+    /// Changes are copied to the member but never from it
+    Follow,
+}
+
+/// Application streams to look for, `app:<pattern>` on the application name or
+/// `binary:<pattern>` on its executable
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum StreamRule {
+    App(String),
+    Binary(String),
+}
+
+/// Property holding the executable of the application a stream belongs to
+pub const BINARY_PROPERTY: &str = "application.process.binary";
+
+impl StreamRule {
+    fn matches_app(&self, name: &str, binary: Option<&str>) -> bool {
+        match self {
+            StreamRule::App(pattern) => matcher::glob(pattern, name),
+            StreamRule::Binary(pattern) => {
+                binary.is_some_and(|binary| matcher::glob(pattern, binary))
+            }
+        }
+    }
+
+    pub fn matches(&self, stream: &StreamDetails) -> bool {
+        self.matches_app(&stream.name, stream.binary.as_deref())
+    }
+
+    /// Whether a recording, named after its application, matches
+    pub fn matches_recording(&self, recording: &SinkDetails) -> bool {
+        let binary = recording.properties.get(BINARY_PROPERTY);
+        self.matches_app(&recording.name, binary.map(String::as_str))
+    }
+}
+
+impl FromStr for StreamRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("app", pattern)) => Ok(StreamRule::App(pattern.to_string())),
+            Some(("binary", pattern)) => Ok(StreamRule::Binary(pattern.to_string())),
+            _ => Err(format!(
+                "invalid stream rule \"{s}\", expected app:<pattern> or binary:<pattern>"
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for StreamRule {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<StreamRule> for String {
+    fn from(rule: StreamRule) -> String {
+        match rule {
+            StreamRule::App(pattern) => format!("app:{pattern}"),
+            StreamRule::Binary(pattern) => format!("binary:{pattern}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupConfig {
+    #[serde(default)]
+    pub sinks: Vec<String>,
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Recording streams to keep in sync with the group, by application
+    #[serde(default)]
+    pub recordings: Vec<StreamRule>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Direction of members by name pattern, members not listed use `Both`
+    #[serde(default)]
+    pub direction: BTreeMap<String, Direction>,
+    #[serde(default, rename = "match")]
+    pub properties: BTreeMap<String, String>,
+    /// Use a dedicated server connection so a slow group can't stall the others
+    #[serde(default)]
+    pub isolated: bool,
+    /// Clamp members that suddenly jump to full volume back to the group level
+    #[serde(default = "default_protect_jumps")]
+    pub protect_jumps: bool,
+    /// Volume in percent at or above which a change may be a jump
+    #[serde(default = "default_jump_threshold")]
+    pub jump_threshold: u32,
+    /// Minimum increase in percent over the group level for a change to count as a jump
+    #[serde(default = "default_jump_size")]
+    pub jump_size: u32,
+    /// Set a member back to the group level when its active port changes
+    #[serde(default)]
+    pub resync_on_port_change: bool,
+    /// Remember the level of every member and apply it as soon as the member reconnects
+    #[serde(default)]
+    pub remember_volumes: bool,
+    /// Set every member to the group's saved level when the daemon starts
+    #[serde(default)]
+    pub restore_on_startup: bool,
+    /// Keep a separate level for every combination of active ports, restored when ports switch
+    #[serde(default)]
+    pub per_port_levels: bool,
+    /// Don't write to suspended members, set them to the group level once they wake up instead
+    #[serde(default)]
+    pub skip_suspended: bool,
+    /// Warn when members are configured with different sample rates
+    #[serde(default)]
+    pub warn_rate_mismatch: bool,
+    /// Member volumes below this snap to 0%
+    pub dead_band_low: Option<Delta>,
+    /// Member volumes this close below 100% snap to exactly 100%
+    pub dead_band_high: Option<Delta>,
+    /// Only take devices assigned to this seat, e.g. `seat0`
+    pub seat: Option<String>,
+    /// Smallest difference from a member's volume worth writing, defaults to the top level one
+    pub min_delta: Option<Delta>,
+    /// Stop syncing while an application stream matching one of these rules is playing
+    #[serde(default)]
+    pub pause_while: Vec<StreamRule>,
+    /// How the group level is computed from the members when one of them changes
+    #[serde(default)]
+    pub strategy: Strategy,
+    /// Whether members take the group level or keep their calibrated offset from it
+    #[serde(default)]
+    pub mode: SyncMode,
+    /// How to pick the change to propagate when several members change at once
+    #[serde(default)]
+    pub arbitration: Arbitration,
+    /// Name patterns of the members that win arbitration, most important first
+    #[serde(default)]
+    pub priority: Vec<String>,
+    /// Translation from the group level to a member's volume by name pattern
+    #[serde(default)]
+    pub translate: BTreeMap<String, Translation>,
+    /// Order in which translation stages run
+    #[serde(default = "default_stages")]
+    pub stages: Vec<Stage>,
+    /// Keep a combine-sink over the group's sinks, so one stream plays on every member
+    #[serde(default)]
+    pub combine: bool,
+    /// Copy the volume of every member sink to its monitor source
+    #[serde(default)]
+    pub sync_monitors: bool,
+    /// Only sync to members with flat volumes, whose volume moves with their loudest stream
+    #[serde(default)]
+    pub flat_volume_follow: bool,
+}
+
+fn default_protect_jumps() -> bool {
+    true
+}
+
+fn default_jump_threshold() -> u32 {
+    100
+}
+
+fn default_jump_size() -> u32 {
+    30
+}
+
+impl Default for GroupConfig {
+    fn default() -> GroupConfig {
+        GroupConfig {
+            sinks: Vec::new(),
+            sources: Vec::new(),
+            recordings: Vec::new(),
+            exclude: Vec::new(),
+            direction: BTreeMap::new(),
+            properties: BTreeMap::new(),
+            isolated: false,
+            protect_jumps: default_protect_jumps(),
+            jump_threshold: default_jump_threshold(),
+            jump_size: default_jump_size(),
+            resync_on_port_change: false,
+            remember_volumes: false,
+            restore_on_startup: false,
+            per_port_levels: false,
+            skip_suspended: false,
+            warn_rate_mismatch: false,
+            dead_band_low: None,
+            dead_band_high: None,
+            seat: None,
+            min_delta: None,
+            pause_while: Vec::new(),
+            strategy: Strategy::default(),
+            mode: SyncMode::default(),
+            arbitration: Arbitration::default(),
+            priority: Vec::new(),
+            translate: BTreeMap::new(),
+            stages: default_stages(),
+            combine: false,
+            sync_monitors: false,
+            flat_volume_follow: false,
+        }
+    }
+}
+
+/// Prefix of the names of the combine-sinks kept for groups
+pub const COMBINE_PREFIX: &str = "volume_sync_combine_";
+
+/// Name of the combine-sink kept for `group`, e.g. `volume_sync_combine_desk`
+pub fn combine_sink_name(group: &str) -> String {
+    format!("{COMBINE_PREFIX}{group}").replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+}
+
+impl GroupConfig {
+    /// Whether a device matches one of the group's name patterns for its kind or, for sinks, all
+    /// of its property patterns, and none of its exclusions; devices on another seat and the
+    /// combine-sinks of groups never match
+    pub fn matches(&self, sink: &SinkDetails) -> bool {
+        if sink.name.starts_with(COMBINE_PREFIX) {
+            return false;
+        }
+        let by_name = match sink.kind {
+            DeviceKind::Sink => self.sinks.iter().any(|p| matcher::glob(p, &sink.name)),
+            DeviceKind::Source => self.sources.iter().any(|p| matcher::glob(p, &sink.name)),
+            DeviceKind::Recording => self
+                .recordings
+                .iter()
+                .any(|rule| rule.matches_recording(sink)),
+        };
+        let by_properties = sink.kind == DeviceKind::Sink
+            && !self.properties.is_empty()
+            && self.properties.iter().all(|(key, pattern)| {
+                sink.properties
+                    .get(key)
+                    .is_some_and(|value| matcher::glob(pattern, value))
+            });
+        let on_seat = match (&self.seat, &sink.seat) {
+            (Some(wanted), Some(seat)) => wanted == seat,
+            _ => true,
+        };
+        (by_name || by_properties)
+            && on_seat
+            && !self.exclude.iter().any(|p| matcher::glob(p, &sink.name))
+    }
+
+    /// Which way changes flow for a member, `Follow` for members with flat volumes if configured
+    pub fn direction(&self, sink: &SinkDetails) -> Direction {
+        if self.flat_volume_follow && sink.flat_volume {
+            return Direction::Follow;
+        }
+        self.direction
+            .iter()
+            .find(|(pattern, _)| matcher::glob(pattern, &sink.name))
+            .map_or(Direction::Both, |(_, direction)| *direction)
+    }
+
+    /// Position of the first `priority` pattern matching member `name`
+    pub fn priority(&self, name: &str) -> Option<usize> {
+        self.priority.iter().position(|p| matcher::glob(p, name))
+    }
+
+    pub fn translation(&self, name: &str) -> Option<&Translation> {
+        self.translate
+            .iter()
+            .find(|(pattern, _)| matcher::glob(pattern, name))
+            .map(|(_, translation)| translation)
+    }
+
+    /// The volume of member `name` at the group `level`
+    pub fn member_volume(&self, name: &str, level: u32) -> u32 {
+        let volume = self
+            .translation(name)
+            .map_or(level, |t| t.apply(&self.stages, level));
+        self.dead_band(volume)
+    }
+
+    /// Snaps a volume inside one of the dead-bands to its edge, 0% or 100%
+    pub fn dead_band(&self, volume: u32) -> u32 {
+        let low = self.dead_band_low.map_or(0, |d| d.volume());
+        let high = self.dead_band_high.map_or(0, |d| d.volume());
+        if volume < low {
+            0
+        } else if volume < VOLUME_NORM && VOLUME_NORM - volume <= high {
+            VOLUME_NORM
+        } else {
+            volume
+        }
+    }
+
+    /// Whether members get anything other than an exact copy of the group level
+    pub fn transforms(&self) -> bool {
+        !self.translate.is_empty()
+            || self.dead_band_low.is_some()
+            || self.dead_band_high.is_some()
+            || self.mode == SyncMode::Relative
+    }
+
+    /// Smallest difference in raw volume worth writing to a member
+    pub fn min_delta(&self) -> u32 {
+        self.min_delta.map_or(0, |d| d.volume())
+    }
+
+    /// The group level that member `name` at `volume` corresponds to
+    pub fn group_level(&self, name: &str, volume: u32) -> u32 {
+        self.translation(name)
+            .map_or(volume, |t| t.invert(&self.stages, volume))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub sinks: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default, rename = "match")]
+    pub properties: BTreeMap<String, String>,
+    /// Sink name patterns to make the default sink, the first one matching a present sink wins
+    #[serde(default)]
+    pub default_priority: Vec<String>,
+    pub log_level: Option<LogFilter>,
+    /// Sound system to sync, read at startup only
+    #[serde(default)]
+    pub backend: BackendKind,
+    /// Server to connect to instead of the default one: a server string, `seat:<seat>` or
+    /// `runtime:<XDG_RUNTIME_DIR>`; read at startup only
+    pub server: Option<String>,
+    /// JACK clients to add a gain stage for, each synced like a sink of the same name
+    #[serde(default)]
+    pub jack_clients: Vec<String>,
+    /// Audio channels of every JACK gain stage
+    #[serde(default = "default_jack_channels")]
+    pub jack_channels: u32,
+    #[serde(default)]
+    pub groups: BTreeMap<String, GroupConfig>,
+    pub journal: Option<String>,
+    /// Log the volumes that would be written instead of writing them
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Number of volume changes kept for `volume-sync history`
+    #[serde(default = "default_history_size")]
+    pub history_size: usize,
+    /// File to append every volume change to as well
+    pub history_file: Option<String>,
+    /// Name of this instance for MQTT topic templates, defaults to the hostname
+    pub instance: Option<String>,
+    /// Set every group back to its level after the system resumes from sleep
+    #[serde(default = "default_resync_on_resume")]
+    pub resync_on_resume: bool,
+    /// Stop listening for events while no group has two members present
+    #[serde(default)]
+    pub idle_suspend: bool,
+    /// Seconds between checks for new devices while idle
+    #[serde(default = "default_idle_poll_interval")]
+    pub idle_poll_interval: u64,
+    /// Set every device back to the volume it had when first seen before exiting
+    #[serde(default)]
+    pub restore_on_exit: bool,
+    /// Seconds to wait for a clean shutdown before exiting anyway
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: u64,
+    /// Show desktop notifications for reconnects, clamped jumps and connection failures
+    #[serde(default)]
+    pub notifications: bool,
+    /// Seconds over which repeated notifications of the same kind are folded into one summary
+    #[serde(default = "default_notification_window")]
+    pub notification_window: u64,
+    /// Milliseconds to collect changes of several members before deciding which one wins, 0 to
+    /// sync every change right away
+    #[serde(default)]
+    pub arbitration_window: u64,
+    /// Seconds between checks of the topology model against a full listing, 0 to disable
+    #[serde(default = "default_validation_interval")]
+    pub validation_interval: u64,
+    /// Put off background work while gamemode or `performance_signal` reports a game running
+    #[serde(default = "default_defer_during_performance")]
+    pub defer_during_performance: bool,
+    /// Session bus signal, as `interface.Member`, whose boolean argument turns performance mode
+    /// on and off; read at startup only
+    pub performance_signal: Option<String>,
+    /// Smallest difference from a member's volume worth writing, for every group
+    pub min_delta: Option<Delta>,
+    /// Stop syncing every group while a matching application stream is playing
+    #[serde(default)]
+    pub pause_while: Vec<StreamRule>,
+    /// File to write the log to as well, for sessions where stderr goes nowhere
+    pub log_file: Option<String>,
+    #[serde(default)]
+    pub log_rotation: LogRotation,
+    /// Size in MiB at which the log file is rotated, never 0 as every line would rotate it
+    #[serde(default = "default_log_max_size")]
+    pub log_max_size: NonZeroU64,
+    /// Number of rotated log files to keep
+    #[serde(default = "default_log_keep")]
+    pub log_keep: usize,
+    /// What 100% stands for in every displayed volume
+    #[serde(default)]
+    pub percent_reference: Reference,
+    /// Named lists of commands run in order by `run <name>`
+    #[serde(default, rename = "macro")]
+    pub macros: BTreeMap<String, Vec<String>>,
+    /// Address to serve the HTTP API on, e.g. `127.0.0.1:7878`, read at startup only
+    pub http_listen: Option<String>,
+    /// Broker to publish group volumes to and take volume commands from, read at startup only
+    pub mqtt: Option<MqttConfig>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Topic every topic of a group starts with, a template with `{hostname}`, `{instance}` and
+    /// `{group}`
+    #[serde(default = "default_mqtt_topic")]
+    pub topic: String,
+    /// Topic announcing whether volume-sync is connected, a template with `{hostname}` and
+    /// `{instance}`
+    #[serde(default = "default_mqtt_status_topic")]
+    pub status_topic: String,
+    /// Prefix Home Assistant reads discovery payloads from, empty to skip discovery
+    #[serde(default = "default_mqtt_discovery_prefix")]
+    pub discovery_prefix: String,
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_topic() -> String {
+    "volume-sync/{instance}/{group}".to_string()
+}
+
+fn default_mqtt_status_topic() -> String {
+    "volume-sync/{instance}/status".to_string()
+}
+
+fn default_mqtt_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+fn default_history_size() -> usize {
+    200
+}
+
+fn default_idle_poll_interval() -> u64 {
+    10
+}
+
+fn default_shutdown_timeout() -> u64 {
+    5
+}
+
+fn default_notification_window() -> u64 {
+    300
+}
+
+fn default_validation_interval() -> u64 {
+    300
+}
+
+fn default_jack_channels() -> u32 {
+    2
+}
+
+fn default_defer_during_performance() -> bool {
+    true
+}
+
+fn default_log_max_size() -> NonZeroU64 {
+    NonZeroU64::new(10).expect("10 is not zero")
+}
+
+fn default_log_keep() -> usize {
+    5
+}
+
+fn default_resync_on_resume() -> bool {
+    true
+}
+
+/// Name of the group formed by the top level `sinks` list
+pub const DEFAULT_GROUP: &str = "default";
+
+impl Default for Config {
+    fn default() -> Config {
+        return Config {
+            sinks: Vec::new(),
+            exclude: Vec::new(),
+            properties: BTreeMap::new(),
+            default_priority: Vec::new(),
+            log_level: Some(LogFilter::Level(LogLevel::Info)),
+            backend: BackendKind::default(),
+            server: None,
+            jack_clients: Vec::new(),
+            jack_channels: default_jack_channels(),
+            groups: BTreeMap::new(),
+            journal: None,
+            dry_run: false,
+            history_size: default_history_size(),
+            history_file: None,
+            instance: None,
+            resync_on_resume: default_resync_on_resume(),
+            idle_suspend: false,
+            idle_poll_interval: default_idle_poll_interval(),
+            restore_on_exit: false,
+            shutdown_timeout: default_shutdown_timeout(),
+            notifications: false,
+            notification_window: default_notification_window(),
+            arbitration_window: 0,
+            validation_interval: default_validation_interval(),
+            defer_during_performance: default_defer_during_performance(),
+            performance_signal: None,
+            min_delta: None,
+            pause_while: Vec::new(),
+            log_file: None,
+            log_rotation: LogRotation::default(),
+            log_max_size: default_log_max_size(),
+            log_keep: default_log_keep(),
+            percent_reference: Reference::default(),
+            macros: BTreeMap::new(),
+            http_listen: None,
+            mqtt: None,
+        };
+    }
+}
+
+impl Config {
+    /// Applies `VOLUME_SYNC_LOG_LEVEL` and `VOLUME_SYNC_SINKS` (comma separated) on top of the file
+    pub fn with_env_overrides(mut self) -> Config {
+        if let Ok(level) = env::var("VOLUME_SYNC_LOG_LEVEL") {
+            match level.parse() {
+                Ok(level) => self.log_level = Some(LogFilter::Level(level)),
+                Err(e) => tracing::warn!("ignoring VOLUME_SYNC_LOG_LEVEL: {e}"),
+            }
+        }
+        if let Ok(sinks) = env::var("VOLUME_SYNC_SINKS") {
+            self.sinks = sinks
+                .split(',')
+                .map(|sink| sink.trim().to_string())
+                .filter(|sink| !sink.is_empty())
+                .collect();
+        }
+        self
+    }
+
+    pub fn groups(&self) -> BTreeMap<String, GroupConfig> {
+        let mut groups = self.groups.clone();
+        if !self.sinks.is_empty() || !self.properties.is_empty() {
+            let group = groups.entry(DEFAULT_GROUP.to_string()).or_default();
+            group.sinks.extend(self.sinks.iter().cloned());
+            group.properties.extend(self.properties.clone());
+        }
+        for group in groups.values_mut() {
+            group.exclude.extend(self.exclude.iter().cloned());
+            group.min_delta = group.min_delta.or(self.min_delta);
+            group.pause_while.extend(self.pause_while.iter().cloned());
+        }
+        groups
+    }
+}
+
+impl LogLevel {
+    fn directive(&self) -> &'static str {
+        match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+}
+
+impl LogFilter {
+    /// The filter as `tracing` directives, e.g. `warn,volume_sync=debug,[{group=desk}]=info`
+    pub fn directives(&self) -> String {
+        match self {
+            LogFilter::Level(level) => level.directive().to_string(),
+            LogFilter::Table {
+                default,
+                group,
+                modules,
+            } => {
+                let default = default.as_ref().unwrap_or(&LogLevel::Info);
+                let mut directives = vec![default.directive().to_string()];
+                for (module, level) in modules {
+                    directives.push(format!("{module}={}", level.directive()));
+                }
+                for (group, level) in group {
+                    directives.push(format!("[{{group={group}}}]={}", level.directive()));
+                }
+                directives.join(",")
+            }
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(LogLevel::Off),
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            _ => Err(format!("invalid log level: {s}")),
+        }
+    }
+}
+
+/// The config file, `$VOLUME_SYNC_CONFIG` if set
+pub fn get_file() -> String {
+    if let Ok(file) = env::var("VOLUME_SYNC_CONFIG") {
+        return file;
+    }
+    let dir = match env::var("XDG_CONFIG_HOME") {
+        Ok(v) => v,
+        Err(_) => match env::var("HOME") {
+            Ok(home) => format!("{home}/.config"),
+            Err(_) => {
+                tracing::error!("failed to load $HOME var");
+                ".".to_string()
+            }
+        },
+    };
+    format!("{dir}/volume-sync.toml")
+}
+
+/// Directory of fragments merged into the main config, in file name order
+pub fn get_dir() -> String {
+    format!("{}.d", get_file())
+}
+
+/// Paths of the `.toml` fragments in the drop-in directory, sorted by name
+pub fn get_fragments() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(get_dir()) else {
+        return Vec::new();
+    };
+    let mut fragments: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    fragments.sort();
+    fragments
+}
+
+/// Merges `other` into `base`: tables are merged key by key, arrays are appended and any other
+/// value replaces the previous one
+fn merge(base: &mut toml::Table, other: toml::Table) {
+    for (key, value) in other {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(a)), toml::Value::Table(b)) => merge(a, b),
+            (Some(toml::Value::Array(a)), toml::Value::Array(b)) => a.extend(b),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Reads a config file, `None` if there is none
+fn read_table(path: &Path) -> Result<Option<toml::Table>, String> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Ok(None);
+    };
+    toml::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// The config file merged with its fragments, `None` if there are none. Files that don't parse
+/// are skipped and reported in `errors`.
+fn read_merged(errors: &mut Vec<String>) -> Option<toml::Table> {
+    let mut paths = vec![PathBuf::from(get_file())];
+    paths.extend(get_fragments());
+    let mut merged: Option<toml::Table> = None;
+    for path in paths {
+        match (read_table(&path), &mut merged) {
+            (Ok(Some(table)), Some(merged)) => merge(merged, table),
+            (Ok(Some(table)), None) => merged = Some(table),
+            (Ok(None), _) => {}
+            (Err(e), _) => errors.push(e),
+        }
+    }
+    merged
+}
+
+/// Removes the key at `path`, e.g. `groups.desk.sink`, returning whether it was there
+fn remove_key(table: &mut toml::Table, path: &[&str]) -> bool {
+    match path {
+        [] => false,
+        [key] => table.remove(*key).is_some(),
+        [key, rest @ ..] => match table.get_mut(*key) {
+            Some(toml::Value::Table(table)) => remove_key(table, rest),
+            _ => false,
+        },
+    }
+}
+
+/// The unknown key an error is about, as its full path, and a hint naming the keys valid in its
+/// place or the one it is most likely a typo of
+fn unknown_key(message: &str) -> Option<(String, String)> {
+    let mut lines = message.lines();
+    let (key, expected) = lines
+        .next()?
+        .strip_prefix("unknown field `")?
+        .split_once('`')?;
+    let table = lines
+        .next()
+        .and_then(|line| line.strip_prefix("in `")?.strip_suffix('`'));
+    let path = match table {
+        Some(table) => format!("{table}.{key}"),
+        None => key.to_string(),
+    };
+    let valid: Vec<&str> = expected.split('`').skip(1).step_by(2).collect();
+    let hint = match matcher::suggest(key, valid.iter().copied()) {
+        Some(name) => format!("did you mean {name}?"),
+        None => format!("expected one of {}", valid.join(", ")),
+    };
+    Some((path, hint))
+}
+
+/// Deserializes a merged config, leaving out and reporting every unknown key rather than failing
+/// on the first. Any other error rejects the config.
+fn parse(mut table: toml::Table) -> Result<(Config, Vec<String>), Vec<String>> {
+    let mut unknown = Vec::new();
+    loop {
+        match table.clone().try_into() {
+            Ok(config) => return Ok((config, unknown)),
+            Err(e) => {
+                let message: String = e.to_string();
+                match unknown_key(&message) {
+                    Some((path, hint))
+                        if remove_key(&mut table, &path.split('.').collect::<Vec<_>>()) =>
+                    {
+                        unknown.push(format!("unknown key {path}, {hint}"));
+                    }
+                    _ => {
+                        unknown.push(message.trim_end().to_string());
+                        return Err(unknown);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reads the config like the daemon does, failing on anything it would skip or reject
+pub fn check_config() -> Result<Config, Vec<String>> {
+    let mut errors = Vec::new();
+    let table = read_merged(&mut errors);
+    let Some(table) = table else {
+        errors.push(format!("no config file found: {}", get_file()));
+        return Err(errors);
+    };
+    match parse(table) {
+        Ok((config, unknown)) if errors.is_empty() && unknown.is_empty() => Ok(config),
+        Ok((_, unknown)) => {
+            errors.extend(unknown);
+            Err(errors)
+        }
+        Err(parse_errors) => {
+            errors.extend(parse_errors);
+            Err(errors)
+        }
+    }
+}
+
+/// Reads the config, `None` if there is none. Unknown keys are left out with a warning; a config
+/// that can't be used without them is rejected with its errors.
+pub fn load_config() -> Result<Option<Config>, Vec<String>> {
+    let mut errors = Vec::new();
+    let table = read_merged(&mut errors);
+    for e in errors {
+        tracing::warn!("ignoring invalid config {e}");
+    }
+    let Some(table) = table else {
+        return Ok(None);
+    };
+    let (config, unknown) = parse(table)?;
+    for key in unknown {
+        tracing::warn!("ignoring {key}");
+    }
+    Ok(Some(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(toml: &str) -> Result<Config, Vec<String>> {
+        parse(toml.parse().expect("invalid TOML")).map(|(config, _)| config)
+    }
+
+    #[test]
+    fn log_max_size_is_never_zero() {
+        assert_eq!(parse_str("").unwrap().log_max_size.get(), 10);
+        assert_eq!(parse_str("log_max_size = 1").unwrap().log_max_size.get(), 1);
+        assert!(parse_str("log_max_size = 0").is_err());
+    }
+
+    #[test]
+    fn dead_band_boundaries() {
+        let group = GroupConfig {
+            dead_band_low: Some("5%".parse().unwrap()),
+            dead_band_high: Some("3%".parse().unwrap()),
+            ..GroupConfig::default()
+        };
+        let low = group.dead_band_low.unwrap().volume();
+        let high = group.dead_band_high.unwrap().volume();
+        let cases = [
+            (0, 0),
+            (low - 1, 0),
+            (low, low),
+            (VOLUME_NORM / 2, VOLUME_NORM / 2),
+            (VOLUME_NORM - high - 1, VOLUME_NORM - high - 1),
+            (VOLUME_NORM - high, VOLUME_NORM),
+            (VOLUME_NORM - 1, VOLUME_NORM),
+            (VOLUME_NORM, VOLUME_NORM),
+            (VOLUME_NORM + 1, VOLUME_NORM + 1),
+        ];
+        for (volume, expected) in cases {
+            assert_eq!(group.dead_band(volume), expected, "{volume}");
+        }
+        let unset = GroupConfig::default();
+        for volume in [0, 1, VOLUME_NORM - 1, VOLUME_NORM, VOLUME_NORM + 1] {
+            assert_eq!(unset.dead_band(volume), volume, "{volume}");
+        }
+    }
+
+    #[test]
+    fn merge_appends_arrays_and_replaces_values() {
+        let mut base: toml::Table = toml::from_str(
+            r#"
+            sinks = ["speakers"]
+            log_keep = 5
+            [groups.desk]
+            sinks = ["dac"]
+            strategy = "copy"
+            "#,
+        )
+        .unwrap();
+        let other: toml::Table = toml::from_str(
+            r#"
+            sinks = ["headphones"]
+            log_keep = 2
+            [groups.desk]
+            strategy = "highest"
+            [groups.couch]
+            sinks = ["tv"]
+            "#,
+        )
+        .unwrap();
+        merge(&mut base, other);
+        let config = parse(base).unwrap().0;
+        assert_eq!(config.sinks, ["speakers", "headphones"]);
+        assert_eq!(config.log_keep, 2);
+        assert_eq!(config.groups["desk"].sinks, ["dac"]);
+        assert_eq!(config.groups["desk"].strategy, Strategy::Highest);
+        assert_eq!(config.groups["couch"].sinks, ["tv"]);
+    }
+
+    /// The only test touching the environment, so nothing runs alongside it reading other values
+    #[test]
+    fn env_overrides_the_files() {
+        let dir = env::temp_dir().join(format!("volume-sync-config-{}", std::process::id()));
+        let file = dir.join("volume-sync.toml");
+        let fragments = dir.join("volume-sync.toml.d");
+        fs::create_dir_all(&fragments).unwrap();
+        fs::write(
+            &file,
+            "sinks = [\"speakers\"]\nlog_keep = 1\nlog_level = \"info\"\n",
+        )
+        .unwrap();
+        fs::write(fragments.join("20-late.toml"), "log_keep = 3\n").unwrap();
+        fs::write(
+            fragments.join("10-early.toml"),
+            "log_keep = 2\nexclude = [\"hdmi\"]\n",
+        )
+        .unwrap();
+        fs::write(fragments.join("30-ignored.conf"), "log_keep = 4\n").unwrap();
+        env::set_var("VOLUME_SYNC_CONFIG", &file);
+
+        let load = || load_config().unwrap().unwrap().with_env_overrides();
+        env::remove_var("VOLUME_SYNC_LOG_LEVEL");
+        env::remove_var("VOLUME_SYNC_SINKS");
+        let config = load();
+        assert_eq!(config.log_keep, 3);
+        assert_eq!(config.sinks, ["speakers"]);
+        assert_eq!(config.exclude, ["hdmi"]);
+        assert!(matches!(
+            config.log_level,
+            Some(LogFilter::Level(LogLevel::Info))
+        ));
+
+        env::set_var("VOLUME_SYNC_LOG_LEVEL", "debug");
+        env::set_var("VOLUME_SYNC_SINKS", " dac, ,headphones ");
+        let config = load();
+        assert_eq!(config.sinks, ["dac", "headphones"]);
+        assert!(matches!(
+            config.log_level,
+            Some(LogFilter::Level(LogLevel::Debug))
+        ));
+
+        // An invalid value is ignored, keeping the one from the file
+        env::set_var("VOLUME_SYNC_LOG_LEVEL", "loud");
+        let config = load();
+        assert!(matches!(
+            config.log_level,
+            Some(LogFilter::Level(LogLevel::Info))
+        ));
+
+        for var in [
+            "VOLUME_SYNC_CONFIG",
+            "VOLUME_SYNC_LOG_LEVEL",
+            "VOLUME_SYNC_SINKS",
+        ] {
+            env::remove_var(var);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
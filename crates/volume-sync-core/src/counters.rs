@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+
+/// Kind of a device, card or stream event received from the server
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    List,
+    New,
+    Changed,
+    Removed,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Operation::List => "list",
+            Operation::New => "new",
+            Operation::Changed => "changed",
+            Operation::Removed => "removed",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Basic health numbers since startup, shown by `volume-sync status`
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Counters {
+    /// Events received, by operation
+    pub events: BTreeMap<Operation, u64>,
+    /// Volumes written to a device
+    pub syncs_applied: u64,
+    /// Writes left out as the volume differed by less than `min_delta`
+    pub syncs_skipped: u64,
+    /// Server connections that became ready again after having been ready before
+    pub reconnects: u64,
+    /// Configs loaded after the first
+    pub config_reloads: u64,
+    /// Replies that couldn't be sent as the client had gone away
+    pub channel_errors: u64,
+}
+
+impl Counters {
+    pub fn event(&mut self, operation: Operation, count: u64) {
+        *self.events.entry(operation).or_default() += count;
+    }
+}
+
+impl fmt::Display for Counters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let events: Vec<String> = self
+            .events
+            .iter()
+            .map(|(operation, count)| format!("{count} {operation}"))
+            .collect();
+        let events = if events.is_empty() {
+            "none".to_string()
+        } else {
+            events.join(", ")
+        };
+        write!(
+            f,
+            "events: {events}; syncs: {} applied, {} skipped; reconnects: {}; config reloads: {}; \
+             channel errors: {}",
+            self.syncs_applied,
+            self.syncs_skipped,
+            self.reconnects,
+            self.config_reloads,
+            self.channel_errors
+        )
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Kinds of notifications that are rate limited together
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Reconnect,
+    Clamp,
+    Connection,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Category::Reconnect => "reconnects",
+            Category::Clamp => "clamped volume jumps",
+            Category::Connection => "connection failures",
+        };
+        write!(f, "{name}")
+    }
+}
+
+struct Bucket {
+    start: u64,
+    suppressed: u32,
+}
+
+/// Shows the first notification of a category right away and folds any more within `window`
+/// seconds into one summary sent when the window ends
+pub struct Digest {
+    window: u64,
+    buckets: BTreeMap<Category, Bucket>,
+}
+
+impl Digest {
+    pub fn new(window: u64) -> Digest {
+        Digest {
+            window,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    pub fn set_window(&mut self, window: u64) {
+        self.window = window;
+    }
+
+    /// Notifications to show now for a `message` at `now` seconds
+    pub fn push(&mut self, now: u64, category: Category, message: String) -> Vec<String> {
+        let mut shown = self.flush(now);
+        match self.buckets.get_mut(&category) {
+            Some(bucket) => bucket.suppressed += 1,
+            None => {
+                self.buckets.insert(
+                    category,
+                    Bucket {
+                        start: now,
+                        suppressed: 0,
+                    },
+                );
+                shown.push(message);
+            }
+        }
+        shown
+    }
+
+    /// Summaries of the categories whose window ended by `now` seconds
+    pub fn flush(&mut self, now: u64) -> Vec<String> {
+        let mut summaries = Vec::new();
+        let window = self.window;
+        self.buckets.retain(|category, bucket| {
+            if now.saturating_sub(bucket.start) < window {
+                return true;
+            }
+            if bucket.suppressed > 0 {
+                summaries.push(format!(
+                    "{} {category} in the last {} minutes",
+                    bucket.suppressed + 1,
+                    window.div_ceil(60)
+                ));
+            }
+            false
+        });
+        summaries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push(digest: &mut Digest, now: u64, category: Category) -> Vec<String> {
+        digest.push(now, category, format!("{category} at {now}"))
+    }
+
+    #[test]
+    fn notifications_within_the_window_are_folded() {
+        let mut digest = Digest::new(300);
+        assert_eq!(
+            push(&mut digest, 100, Category::Clamp),
+            ["clamped volume jumps at 100"]
+        );
+        assert!(push(&mut digest, 150, Category::Clamp).is_empty());
+        assert!(push(&mut digest, 399, Category::Clamp).is_empty());
+        assert!(digest.flush(399).is_empty());
+        assert_eq!(
+            digest.flush(400),
+            ["3 clamped volume jumps in the last 5 minutes"]
+        );
+        assert!(digest.flush(1000).is_empty());
+        assert_eq!(
+            push(&mut digest, 1000, Category::Clamp),
+            ["clamped volume jumps at 1000"]
+        );
+    }
+
+    #[test]
+    fn categories_have_windows_of_their_own() {
+        let mut digest = Digest::new(90);
+        assert_eq!(push(&mut digest, 0, Category::Reconnect).len(), 1);
+        assert!(push(&mut digest, 10, Category::Reconnect).is_empty());
+        assert_eq!(
+            push(&mut digest, 50, Category::Connection),
+            ["connection failures at 50"]
+        );
+        // The summary comes with the next notification once the window has ended
+        assert_eq!(
+            push(&mut digest, 100, Category::Connection),
+            ["2 reconnects in the last 2 minutes"]
+        );
+        assert_eq!(
+            digest.flush(140),
+            ["2 connection failures in the last 2 minutes"]
+        );
+    }
+
+    #[test]
+    fn single_notifications_and_no_window_are_never_summarized() {
+        let mut digest = Digest::new(60);
+        assert_eq!(push(&mut digest, 0, Category::Clamp).len(), 1);
+        assert!(digest.flush(60).is_empty());
+
+        digest.set_window(0);
+        for now in [100, 100, 101] {
+            assert_eq!(push(&mut digest, now, Category::Clamp).len(), 1, "{now}");
+        }
+        assert!(digest.flush(101).is_empty());
+    }
+}
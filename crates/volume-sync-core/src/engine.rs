@@ -0,0 +1,2217 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::command::{Command, Reply};
+use crate::config::{Arbitration, Config, Direction, GroupConfig, Strategy, SyncMode};
+use crate::counters::{Counters, Operation};
+use crate::digest::Category;
+use crate::event::SHARED_CONTEXT;
+use crate::format;
+use crate::graph::{Edge, Flow, Graph, Node, Shape};
+use crate::group::{self, GroupState, Members, Mode, Snapshot, SNAPSHOT_VERSION};
+use crate::history::{Change, History, Trigger};
+use crate::matcher;
+use crate::sink::{DeviceId, DeviceKind, SinkDetails};
+use crate::state::State;
+use crate::topology::{CardDetails, StreamDetails, Topology};
+use crate::volume::{percent_to_volume, VolumeSpec, VOLUME_MAX};
+
+/// Everything the engine reacts to. Inputs are journaled so a run can be replayed exactly.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Input {
+    ConfigLoaded(Box<Config>),
+    /// A config reloaded off the event loop, with the members of its groups among the devices
+    /// known at `generation`
+    ConfigReloaded {
+        config: Box<Config>,
+        members: Members,
+        generation: u64,
+    },
+    StateLoaded(State),
+    SinkList(Vec<SinkDetails>),
+    SinkNew(SinkDetails),
+    SinkChanged(SinkDetails),
+    SinkRemoved(u32),
+    SourceList(Vec<SinkDetails>),
+    SourceNew(SinkDetails),
+    SourceChanged(SinkDetails),
+    SourceRemoved(u32),
+    RecordingList(Vec<SinkDetails>),
+    RecordingNew(SinkDetails),
+    RecordingChanged(SinkDetails),
+    RecordingRemoved(u32),
+    /// Changes of several devices received within the arbitration window, oldest first
+    Changes(Vec<SinkDetails>),
+    CardList(Vec<CardDetails>),
+    CardChanged(CardDetails),
+    CardRemoved(u32),
+    StreamList(Vec<StreamDetails>),
+    StreamChanged(StreamDetails),
+    StreamRemoved(u32),
+    ContextState {
+        context: String,
+        state: String,
+    },
+    WatcherHealth(String),
+    /// Whether performance mode is on and background work is put off
+    Performance(bool),
+    /// Current time in seconds since the epoch, expiring pause leases
+    Tick(u64),
+    Resumed,
+    Command(Command),
+    Shutdown,
+}
+
+/// Side effects requested by the engine, executed by the caller.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Action {
+    /// Copy the volume of `from` to every device in `to`
+    SyncVolume {
+        group: String,
+        from: DeviceId,
+        to: Vec<DeviceId>,
+        /// Skip the write when the volumes differ by less than this
+        min_delta: u32,
+    },
+    SetVolume {
+        group: String,
+        devices: Vec<DeviceId>,
+        volume: VolumeSpec,
+    },
+    RestoreVolume {
+        group: String,
+        device: DeviceId,
+        volume: u32,
+    },
+    SaveState(State),
+    /// Whether no group has enough members present for events to matter
+    SetIdle(bool),
+    /// Fetch every card, device and stream again, e.g. after a card switched profiles
+    Refresh,
+    Notify {
+        category: Category,
+        message: String,
+    },
+    /// Keep a combine-sink over the sinks named `sinks`, removing it when empty
+    Combine {
+        group: String,
+        sinks: Vec<String>,
+    },
+    /// Make the sink named so the server's default sink
+    SetDefaultSink(String),
+    /// Append volume changes to the history file
+    AppendHistory {
+        file: String,
+        changes: Vec<Change>,
+    },
+    /// Replace the log filter, replying whether the directives were valid
+    SetLogFilter(String),
+    Reply(Reply),
+}
+
+/// Pure state machine deciding which devices to sync; it never talks to the server itself.
+#[derive(Debug, Default)]
+pub struct Engine {
+    groups: BTreeMap<String, GroupConfig>,
+    topology: Topology,
+    group_states: BTreeMap<String, GroupState>,
+    /// Volumes written by a group strategy whose change events are only echoes
+    expected: BTreeMap<DeviceId, u32>,
+    contexts: BTreeMap<String, String>,
+    watcher: Option<String>,
+    performance: bool,
+    resync_on_resume: bool,
+    restore_on_exit: bool,
+    /// Volume of every device when it was first seen, restored on exit
+    original: BTreeMap<DeviceId, u32>,
+    idle_suspend: bool,
+    idle: bool,
+    state: State,
+    macros: BTreeMap<String, Vec<String>>,
+    /// Whether the names in the config are yet to be checked against the next full listing
+    check_names: bool,
+    /// Members of the combine-sink of every group that has one
+    combined: BTreeMap<String, Vec<String>>,
+    /// Names of the flat volume members already warned about
+    flat_warned: BTreeSet<String>,
+    /// Sink name patterns in the order they are preferred as the default sink
+    default_priority: Vec<String>,
+    /// The sink last made the default one
+    default_sink: Option<String>,
+    /// Time of the last tick
+    now: u64,
+    history: History,
+    history_file: Option<String>,
+    /// Volumes written and recorded in the history, whose change events are only echoes
+    written: BTreeMap<DeviceId, u32>,
+    counters: Counters,
+    /// Contexts that have been ready at least once, so being ready again is a reconnect
+    ready: BTreeSet<String>,
+    config_loaded: bool,
+    /// Bumped whenever a device comes, goes or changes what groups it could match
+    generation: u64,
+}
+
+/// Moves `volume` by a signed `offset`, within the valid range
+fn apply_offset(volume: u32, offset: i64) -> u32 {
+    (volume as i64 + offset).clamp(0, VOLUME_MAX as i64) as u32
+}
+
+/// The operation of a server event and how many devices, cards or streams it is about
+fn operation(input: &Input) -> Option<(Operation, u64)> {
+    let operation = match input {
+        Input::SinkList(_)
+        | Input::SourceList(_)
+        | Input::RecordingList(_)
+        | Input::CardList(_)
+        | Input::StreamList(_) => Operation::List,
+        Input::SinkNew(_) | Input::SourceNew(_) | Input::RecordingNew(_) => Operation::New,
+        Input::Changes(devices) => return Some((Operation::Changed, devices.len() as u64)),
+        Input::SinkChanged(_)
+        | Input::SourceChanged(_)
+        | Input::RecordingChanged(_)
+        | Input::CardChanged(_)
+        | Input::StreamChanged(_) => Operation::Changed,
+        Input::SinkRemoved(_)
+        | Input::SourceRemoved(_)
+        | Input::RecordingRemoved(_)
+        | Input::CardRemoved(_)
+        | Input::StreamRemoved(_) => Operation::Removed,
+        _ => return None,
+    };
+    Some((operation, 1))
+}
+
+/// Whether a change to `volume` looks like a device glitch jumping to full volume rather than a
+/// deliberate change from the group's `level`
+fn is_jump(group_config: &GroupConfig, level: u32, volume: u32) -> bool {
+    group_config.protect_jumps
+        && volume >= percent_to_volume(group_config.jump_threshold)
+        && volume.saturating_sub(level) >= percent_to_volume(group_config.jump_size)
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine::default()
+    }
+
+    pub fn handle(&mut self, input: Input) -> Vec<Action> {
+        let trigger = match &input {
+            Input::Command(_) => Trigger::Command,
+            Input::ConfigLoaded(_)
+            | Input::ConfigReloaded { .. }
+            | Input::StateLoaded(_)
+            | Input::SinkList(_)
+            | Input::SourceList(_)
+            | Input::RecordingList(_) => Trigger::Startup,
+            _ => Trigger::Sync,
+        };
+        if let Some((operation, count)) = operation(&input) {
+            self.counters.event(operation, count);
+        }
+        let mut changes = self.external_changes(&input);
+        let mut actions = self.dispatch(input);
+        let idle = self.idle_suspend
+            && self
+                .group_states
+                .values()
+                .all(|group_state| group_state.members.len() < 2);
+        if idle != self.idle {
+            self.idle = idle;
+            actions.push(Action::SetIdle(idle));
+        }
+        actions.extend(self.update_combines());
+        actions.extend(self.update_default_sink());
+        let written = self.written_changes(trigger, &actions);
+        self.counters.syncs_applied += written.len() as u64;
+        changes.extend(written);
+        if !changes.is_empty() {
+            for change in &changes {
+                self.history.push(change.clone());
+            }
+            if let Some(file) = &self.history_file {
+                actions.push(Action::AppendHistory {
+                    file: file.clone(),
+                    changes,
+                });
+            }
+        }
+        actions
+    }
+
+    /// Counts replies that couldn't be sent, as the engine never sends them itself
+    pub fn count_channel_errors(&mut self, count: u64) {
+        self.counters.channel_errors += count;
+    }
+
+    /// The first group `id` is a member of
+    fn group_of(&self, id: DeviceId) -> Option<String> {
+        self.group_states
+            .iter()
+            .find(|(_, group_state)| group_state.members.contains(&id))
+            .map(|(group, _)| group.clone())
+    }
+
+    /// Changes of known devices in `input` that volume-sync didn't write itself
+    fn external_changes(&mut self, input: &Input) -> Vec<Change> {
+        let devices = match input {
+            Input::SinkChanged(device)
+            | Input::SourceChanged(device)
+            | Input::RecordingChanged(device) => std::slice::from_ref(device),
+            Input::Changes(devices) => devices.as_slice(),
+            _ => return Vec::new(),
+        };
+        let mut changes = Vec::new();
+        for device in devices {
+            let id = device.id();
+            let Some(old) = self.topology.devices.get(&id).map(|known| known.volume) else {
+                continue;
+            };
+            if self.written.remove(&id) == Some(device.volume) || old == device.volume {
+                continue;
+            }
+            changes.push(Change {
+                time: self.now,
+                group: self.group_of(id),
+                device: device.name.clone(),
+                old,
+                new: device.volume,
+                trigger: Trigger::External,
+            });
+        }
+        changes
+    }
+
+    /// Changes `actions` are about to write, remembered so their echoes aren't taken for external
+    /// changes
+    fn written_changes(&mut self, trigger: Trigger, actions: &[Action]) -> Vec<Change> {
+        let devices = &self.topology.devices;
+        let volume_of = |id: &DeviceId| devices.get(id).map(|d| d.volume);
+        let mut writes = Vec::new();
+        for action in actions {
+            match action {
+                Action::SyncVolume {
+                    group,
+                    from,
+                    to,
+                    min_delta,
+                } => {
+                    let Some(volume) = volume_of(from) else {
+                        continue;
+                    };
+                    for id in to {
+                        let delta = volume_of(id).map_or(0, |old| old.abs_diff(volume));
+                        if delta >= *min_delta {
+                            writes.push((group, *id, volume));
+                        } else if delta > 0 {
+                            self.counters.syncs_skipped += 1;
+                        }
+                    }
+                }
+                Action::SetVolume {
+                    group,
+                    devices,
+                    volume,
+                } => {
+                    for id in devices {
+                        if let Some(old) = volume_of(id) {
+                            writes.push((group, *id, volume.apply(old)));
+                        }
+                    }
+                }
+                Action::RestoreVolume {
+                    group,
+                    device,
+                    volume,
+                } => writes.push((group, *device, *volume)),
+                _ => {}
+            }
+        }
+        let mut changes = Vec::new();
+        for (group, id, new) in writes {
+            let Some(device) = self.topology.devices.get(&id) else {
+                continue;
+            };
+            if device.volume == new {
+                continue;
+            }
+            changes.push(Change {
+                time: self.now,
+                group: Some(group.clone()),
+                device: device.name.clone(),
+                old: device.volume,
+                new,
+                trigger,
+            });
+            self.written.insert(id, new);
+        }
+        changes
+    }
+
+    /// Makes the present sink matching the earliest `default_priority` pattern the default sink,
+    /// whenever that changes
+    fn update_default_sink(&mut self) -> Option<Action> {
+        let preferred = self.default_priority.iter().find_map(|pattern| {
+            self.topology.devices.values().find(|device| {
+                device.kind == DeviceKind::Sink && matcher::glob(pattern, &device.name)
+            })
+        });
+        let name = preferred.map(|device| device.name.clone());
+        if self.default_sink == name {
+            return None;
+        }
+        // Forgotten once no preferred sink is left, so it is set again when one comes back
+        self.default_sink = name;
+        let preferred = preferred?;
+        tracing::info!("making {} the default sink", preferred.label());
+        Some(Action::SetDefaultSink(preferred.name.clone()))
+    }
+
+    /// Loads, replaces or removes the combine-sinks of groups whose sinks came or went
+    fn update_combines(&mut self) -> Vec<Action> {
+        let mut wanted = BTreeMap::new();
+        for (group, group_config) in &self.groups {
+            let Some(group_state) = self.group_states.get(group) else {
+                continue;
+            };
+            if !group_config.combine {
+                continue;
+            }
+            let sinks: Vec<String> = group_state
+                .members
+                .iter()
+                .filter(|id| id.kind == DeviceKind::Sink)
+                .filter_map(|id| self.topology.devices.get(id))
+                .map(|device| device.name.clone())
+                .collect();
+            if sinks.len() >= 2 {
+                wanted.insert(group.clone(), sinks);
+            }
+        }
+        let mut actions = Vec::new();
+        for group in self.combined.keys() {
+            if !wanted.contains_key(group) {
+                tracing::info!("{group}: removing combine-sink");
+                actions.push(Action::Combine {
+                    group: group.clone(),
+                    sinks: Vec::new(),
+                });
+            }
+        }
+        for (group, sinks) in &wanted {
+            if self.combined.get(group) != Some(sinks) {
+                tracing::info!("{group}: combining {}", sinks.join(", "));
+                actions.push(Action::Combine {
+                    group: group.clone(),
+                    sinks: sinks.clone(),
+                });
+            }
+        }
+        self.combined = wanted;
+        actions
+    }
+
+    fn dispatch(&mut self, input: Input) -> Vec<Action> {
+        match input {
+            Input::ConfigLoaded(config) => {
+                self.load_config(*config);
+                self.update_members();
+                Vec::new()
+            }
+            Input::ConfigReloaded {
+                config,
+                members,
+                generation,
+            } => {
+                self.load_config(*config);
+                if generation == self.generation {
+                    self.set_members(members);
+                } else {
+                    tracing::debug!("devices changed during the reload, matching them again");
+                    self.update_members();
+                }
+                Vec::new()
+            }
+            Input::StateLoaded(state) => {
+                for (group, level) in &state.groups {
+                    if let Some(group_state) = self.group_states.get_mut(group) {
+                        group_state.level.get_or_insert(*level);
+                    }
+                }
+                self.state = state;
+                Vec::new()
+            }
+            Input::SinkList(sinks) => self.device_list(DeviceKind::Sink, sinks),
+            Input::SinkNew(sink) => self.device_new(sink),
+            Input::SinkChanged(sink) => self.device_changed(sink, &BTreeSet::new()),
+            Input::SinkRemoved(index) => {
+                self.device_removed(DeviceId::sink(index));
+                Vec::new()
+            }
+            Input::SourceList(sources) => self.device_list(DeviceKind::Source, sources),
+            Input::SourceNew(source) => self.device_new(source),
+            Input::SourceChanged(source) => self.device_changed(source, &BTreeSet::new()),
+            Input::RecordingList(recordings) => self.device_list(DeviceKind::Recording, recordings),
+            Input::RecordingNew(recording) => self.device_new(recording),
+            Input::RecordingChanged(recording) => self.device_changed(recording, &BTreeSet::new()),
+            Input::RecordingRemoved(index) => {
+                self.device_removed(DeviceId::recording(index));
+                Vec::new()
+            }
+            Input::Changes(devices) => self.devices_changed(devices),
+            Input::SourceRemoved(index) => {
+                self.device_removed(DeviceId::source(index));
+                Vec::new()
+            }
+            Input::CardList(cards) => {
+                self.topology.set_cards(cards);
+                Vec::new()
+            }
+            Input::CardChanged(card) => {
+                let (index, profile) = (card.index, card.active_profile.clone());
+                if !self.topology.update_card(card) {
+                    return Vec::new();
+                }
+                tracing::info!("card {index} profile changed to {profile:?}");
+                vec![Action::Refresh]
+            }
+            Input::CardRemoved(index) => {
+                self.topology.remove_card(index);
+                Vec::new()
+            }
+            Input::StreamList(streams) => {
+                let before = self.app_pauses();
+                self.topology.set_streams(streams);
+                self.log_app_pauses(before);
+                // Streams are listed last, so every device of the listing is known by now
+                if self.check_names {
+                    self.check_names = false;
+                    for finding in self.unmatched() {
+                        tracing::warn!("{finding}");
+                    }
+                }
+                Vec::new()
+            }
+            Input::StreamChanged(stream) => {
+                let before = self.app_pauses();
+                self.topology.update_stream(stream);
+                self.log_app_pauses(before);
+                Vec::new()
+            }
+            Input::StreamRemoved(index) => {
+                let before = self.app_pauses();
+                self.topology.remove_stream(index);
+                self.log_app_pauses(before);
+                Vec::new()
+            }
+            Input::ContextState { context, state } => {
+                let message = format!("connection {context} {}", state.to_lowercase());
+                let failed = matches!(state.as_str(), "Failed" | "Terminated");
+                if state == "Ready" && !self.ready.insert(context.clone()) {
+                    self.counters.reconnects += 1;
+                }
+                self.contexts.insert(context, state);
+                if !failed {
+                    return Vec::new();
+                }
+                vec![Action::Notify {
+                    category: Category::Connection,
+                    message,
+                }]
+            }
+            Input::WatcherHealth(health) => {
+                self.watcher = Some(health);
+                Vec::new()
+            }
+            Input::Performance(active) => {
+                self.performance = active;
+                Vec::new()
+            }
+            Input::Tick(now) => {
+                self.now = now;
+                self.expire_pauses();
+                Vec::new()
+            }
+            Input::Resumed if self.resync_on_resume => self
+                .group_states
+                .keys()
+                .flat_map(|group| self.realign(group))
+                .collect(),
+            Input::Resumed => Vec::new(),
+            Input::Command(command) => self.handle_command(command),
+            Input::Shutdown => self.shutdown(),
+        }
+    }
+
+    fn load_config(&mut self, config: Config) {
+        if self.config_loaded {
+            self.counters.config_reloads += 1;
+        }
+        self.config_loaded = true;
+        self.groups = config.groups();
+        self.idle_suspend = config.idle_suspend;
+        self.resync_on_resume = config.resync_on_resume;
+        self.restore_on_exit = config.restore_on_exit;
+        self.macros = config.macros;
+        self.default_priority = config.default_priority;
+        self.history.set_capacity(config.history_size);
+        self.history_file = config.history_file;
+        self.check_names = true;
+    }
+
+    /// Puts devices back the way they were found, if configured, and saves the state one last time
+    fn shutdown(&self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        if self.restore_on_exit {
+            let mut restored = BTreeSet::new();
+            for (group, group_state) in &self.group_states {
+                for id in &group_state.members {
+                    if !restored.insert(*id) {
+                        continue;
+                    }
+                    if let Some(volume) = self.original.get(id) {
+                        actions.push(Action::RestoreVolume {
+                            group: group.clone(),
+                            device: *id,
+                            volume: *volume,
+                        });
+                    }
+                }
+            }
+        }
+        actions.push(Action::SaveState(self.state.clone()));
+        actions
+    }
+
+    /// Sets every member that follows the group back to the group level
+    fn realign(&self, group: &str) -> Vec<Action> {
+        let (Some(group_config), Some(group_state)) =
+            (self.groups.get(group), self.group_states.get(group))
+        else {
+            return Vec::new();
+        };
+        let Some(level) = group_state.level else {
+            return Vec::new();
+        };
+        group_state
+            .members
+            .iter()
+            .filter(|id| group_config.direction(&self.topology.devices[id]) != Direction::Lead)
+            .filter(|id| !(group_config.skip_suspended && self.topology.devices[id].suspended))
+            .map(|id| Action::RestoreVolume {
+                group: group.to_string(),
+                device: *id,
+                volume: self.member_volume(group, &self.topology.devices[id], level),
+            })
+            .collect()
+    }
+
+    fn device_list(&mut self, kind: DeviceKind, devices: Vec<SinkDetails>) -> Vec<Action> {
+        for device in &devices {
+            self.original.entry(device.id()).or_insert(device.volume);
+        }
+        self.topology.set_devices(kind, devices);
+        self.generation += 1;
+        self.original
+            .retain(|id, _| self.topology.devices.contains_key(id));
+        self.update_members();
+        self.check_rates();
+        self.check_flat_volumes();
+        let mut actions = self.restore_saved_levels();
+        if self.capture_offsets() {
+            actions.push(Action::SaveState(self.state.clone()));
+        }
+        let members: BTreeSet<DeviceId> = self
+            .group_states
+            .values()
+            .flat_map(|group_state| group_state.members.iter().copied())
+            .collect();
+        for id in members {
+            actions.extend(self.sync_monitors(id));
+        }
+        actions
+    }
+
+    /// Copies the volume of a member sink to its monitor source in the groups that want it,
+    /// unless they are paused
+    fn sync_monitors(&self, id: DeviceId) -> Vec<Action> {
+        let Some(sink) = self.topology.devices.get(&id) else {
+            return Vec::new();
+        };
+        let Some(monitor) = sink.monitor.map(DeviceId::source) else {
+            return Vec::new();
+        };
+        if self
+            .topology
+            .devices
+            .get(&monitor)
+            .is_none_or(|source| source.volume == sink.volume)
+        {
+            return Vec::new();
+        }
+        let group = self.group_states.iter().find(|(group, group_state)| {
+            group_state.members.contains(&id)
+                && self.groups.get(*group).is_some_and(|c| c.sync_monitors)
+                && !self.is_paused(group)
+        });
+        let Some((group, _)) = group else {
+            return Vec::new();
+        };
+        tracing::debug!(
+            "{group}: copying the volume of {} to its monitor",
+            sink.label()
+        );
+        vec![Action::RestoreVolume {
+            group: group.clone(),
+            device: monitor,
+            volume: sink.volume,
+        }]
+    }
+
+    /// Sets groups back to the level saved by a previous run, once per group
+    fn restore_saved_levels(&mut self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        for (group, group_config) in &self.groups {
+            let Some(group_state) = self.group_states.get_mut(group) else {
+                continue;
+            };
+            if !group_config.restore_on_startup
+                || group_state.restored
+                || group_state.members.is_empty()
+            {
+                continue;
+            }
+            let Some(level) = self.state.groups.get(group) else {
+                continue;
+            };
+            tracing::info!("{group}: restoring saved level {}", format::volume(*level));
+            group_state.level = Some(*level);
+            group_state.restored = true;
+            actions.extend(self.realign(group));
+        }
+        actions
+    }
+
+    fn device_new(&mut self, device: SinkDetails) -> Vec<Action> {
+        tracing::info!("added {} {}", device.id(), device.label());
+        let mut actions = Vec::new();
+        for (group, group_config) in &self.groups {
+            if group_config.matches(&device) {
+                let paused = self.is_paused(group);
+                let offset = self.offset(group, &device);
+                let group_state = self.group_states.entry(group.clone()).or_default();
+                group_state.members.insert(device.id());
+                actions.push(Action::Notify {
+                    category: Category::Reconnect,
+                    message: format!("{} connected to {group}", device.display_name()),
+                });
+                let Some(level) = group_state.level else {
+                    let volume = apply_offset(device.volume, -offset.unwrap_or(0));
+                    group_state.level = Some(group_config.group_level(&device.name, volume));
+                    continue;
+                };
+                let Some(offset) = offset else {
+                    let offset = device.volume as i64
+                        - group_config.member_volume(&device.name, level) as i64;
+                    tracing::info!(
+                        "{group}: keeping {} at {} from the group level",
+                        device.label(),
+                        format::offset(offset)
+                    );
+                    self.state
+                        .offsets
+                        .entry(group.clone())
+                        .or_default()
+                        .insert(device.stable_id(), offset);
+                    actions.push(Action::SaveState(self.state.clone()));
+                    continue;
+                };
+                if group_config.remember_volumes {
+                    if let Some(volume) = self.state.devices.get(&device.stable_id()) {
+                        tracing::info!(
+                            "{group}: restoring remembered volume {} for {}",
+                            format::volume(*volume),
+                            device.name
+                        );
+                        actions.push(Action::RestoreVolume {
+                            group: group.clone(),
+                            device: device.id(),
+                            volume: *volume,
+                        });
+                        continue;
+                    }
+                }
+                // A device joining a group takes on its level rather than waiting for the next change
+                if paused
+                    || group_config.direction(&device) == Direction::Lead
+                    || (group_config.skip_suspended && device.suspended)
+                {
+                    continue;
+                }
+                let volume = group_config.member_volume(&device.name, level);
+                let volume = device.round_to_step(apply_offset(volume, offset));
+                let delta = volume.abs_diff(device.volume);
+                if delta == 0 || delta < group_config.min_delta() {
+                    if delta > 0 {
+                        self.counters.syncs_skipped += 1;
+                    }
+                    continue;
+                }
+                tracing::info!(
+                    "{group}: setting {} to the group level {}",
+                    device.label(),
+                    format::volume(level)
+                );
+                if group_config.strategy != Strategy::Copy {
+                    self.expected.insert(device.id(), volume);
+                }
+                actions.push(Action::RestoreVolume {
+                    group: group.clone(),
+                    device: device.id(),
+                    volume,
+                });
+            }
+        }
+        let id = device.id();
+        self.original.entry(id).or_insert(device.volume);
+        self.topology.devices.insert(id, device);
+        self.generation += 1;
+        self.check_flat_volumes();
+        actions.extend(self.sync_monitors(id));
+        actions
+    }
+
+    /// Propagates only the winning change of each group, the other members are overwritten by it
+    fn devices_changed(&mut self, devices: Vec<SinkDetails>) -> Vec<Action> {
+        let mut winners: BTreeMap<String, DeviceId> = BTreeMap::new();
+        for (group, group_state) in &self.group_states {
+            let members = &group_state.members;
+            let group_config = &self.groups[group];
+            let mut candidates = devices.iter().filter(|device| {
+                members.contains(&device.id())
+                    && group_config.direction(device) != Direction::Follow
+            });
+            let winner = match group_config.arbitration {
+                Arbitration::LastWriter => candidates.next_back(),
+                Arbitration::Highest => candidates.max_by_key(|device| device.volume),
+                Arbitration::Priority => candidates
+                    .rev()
+                    .min_by_key(|device| group_config.priority(&device.name).unwrap_or(usize::MAX)),
+            };
+            if let Some(winner) = winner {
+                winners.insert(group.clone(), winner.id());
+            }
+        }
+        if winners.len() < devices.len() {
+            tracing::info!("arbitrated {} changes: {winners:?}", devices.len());
+        }
+
+        let mut actions = Vec::new();
+        for device in devices {
+            let lost = winners
+                .iter()
+                .filter(|(_, winner)| **winner != device.id())
+                .map(|(group, _)| group.clone())
+                .collect();
+            actions.extend(self.device_changed(device, &lost));
+        }
+        actions
+    }
+
+    /// Handles a change of `device`, which isn't propagated in the groups it `lost` arbitration in
+    fn device_changed(&mut self, device: SinkDetails, lost: &BTreeSet<String>) -> Vec<Action> {
+        let (id, volume) = (device.id(), device.volume);
+        let port_changed = self
+            .topology
+            .devices
+            .get(&id)
+            .is_some_and(|previous| previous.active_port != device.active_port);
+        let woke_up = !device.suspended
+            && self
+                .topology
+                .devices
+                .get(&id)
+                .is_some_and(|previous| previous.suspended);
+        if port_changed {
+            tracing::info!("{id} port changed to {:?}", device.active_port);
+        }
+        let old = self
+            .topology
+            .devices
+            .get(&id)
+            .map(|previous| previous.volume);
+        let name = device.name.clone();
+        let rematch = self.topology.devices.get(&id).is_none_or(|previous| {
+            previous.name != device.name
+                || previous.properties != device.properties
+                || previous.seat != device.seat
+        });
+        if rematch {
+            self.generation += 1;
+        }
+        self.topology.devices.insert(id, device);
+
+        let mut actions = self.sync_monitors(id);
+        let mut state_changed = false;
+        let groups: Vec<(String, BTreeSet<DeviceId>)> = self
+            .group_states
+            .iter()
+            .filter(|(group, group_state)| {
+                group_state.members.contains(&id) && !lost.contains(*group)
+            })
+            .map(|(group, group_state)| (group.clone(), group_state.members.clone()))
+            .collect();
+        for (group, members) in &groups {
+            let _span = tracing::info_span!("group", %group).entered();
+            if self.is_paused(group) {
+                tracing::debug!("{group}: paused, not syncing {id}");
+                continue;
+            }
+            let Some(group_config) = self.groups.get(group) else {
+                continue;
+            };
+            tracing::debug!(
+                %group,
+                sink = %name,
+                index = id.index,
+                old = old.map(format::volume),
+                new = %format::volume(volume),
+                "member changed"
+            );
+            let copy = group_config.strategy == Strategy::Copy;
+            let step = self.topology.devices[&id].volume_step().unwrap_or(0);
+            let echo = |expected: &u32| expected.abs_diff(volume) <= step / 2;
+            if !copy && self.expected.get(&id).is_some_and(echo) {
+                self.expected.remove(&id);
+                continue;
+            }
+            if port_changed && group_config.per_port_levels {
+                let key = self.port_key(members);
+                if let Some(level) = self.state.ports.get(group).and_then(|p| p.get(&key)) {
+                    tracing::info!(
+                        "{group}: restoring level {} for ports {key}",
+                        format::volume(*level)
+                    );
+                    if let Some(group_state) = self.group_states.get_mut(group) {
+                        group_state.level = Some(*level);
+                    }
+                    actions.extend(self.realign(group));
+                    continue;
+                }
+            }
+            let name = &self.topology.devices[&id].name;
+            if let Some(level) = self.group_states[group].level {
+                let expected = self.member_volume(group, &self.topology.devices[&id], level);
+                if woke_up && group_config.skip_suspended {
+                    if volume != expected {
+                        tracing::info!("{group}: {id} woke up, setting it to the group level");
+                        actions.push(Action::RestoreVolume {
+                            group: group.clone(),
+                            device: id,
+                            volume: expected,
+                        });
+                    }
+                    continue;
+                }
+                if port_changed && group_config.resync_on_port_change {
+                    actions.push(Action::RestoreVolume {
+                        group: group.clone(),
+                        device: id,
+                        volume: expected,
+                    });
+                    continue;
+                }
+                if is_jump(group_config, expected, volume) {
+                    tracing::warn!(
+                        "{group}: {id} jumped to {}, clamping back to {}",
+                        format::volume(volume),
+                        format::volume(expected)
+                    );
+                    actions.push(Action::RestoreVolume {
+                        group: group.clone(),
+                        device: id,
+                        volume: expected,
+                    });
+                    actions.push(Action::Notify {
+                        category: Category::Clamp,
+                        message: format!(
+                            "{name} jumped to {}, set back to {}",
+                            format::volume(volume),
+                            format::volume(expected)
+                        ),
+                    });
+                    continue;
+                }
+                // A hardware volume device lands on the step nearest to what was written, or on
+                // the step itself, which is no change of its own
+                if step > 0 && volume.abs_diff(expected) <= step / 2 {
+                    tracing::debug!(
+                        "{group}: {id} settled at {}, within a step of {}",
+                        format::volume(volume),
+                        format::volume(expected)
+                    );
+                    continue;
+                }
+            }
+            if group_config.direction(&self.topology.devices[&id]) == Direction::Follow {
+                continue;
+            }
+            let level = match group_config.strategy {
+                Strategy::Copy => self.group_level(group, &self.topology.devices[&id], volume),
+                strategy => self.aggregate(group, strategy, members),
+            };
+            if let Some(group_state) = self.group_states.get_mut(group) {
+                group_state.level = Some(level);
+            }
+            state_changed |= self.state.groups.insert(group.clone(), level) != Some(level);
+            if group_config.per_port_levels {
+                let key = self.port_key(members);
+                let ports = self.state.ports.entry(group.clone()).or_default();
+                state_changed |= ports.insert(key, level) != Some(level);
+            }
+            if group_config.remember_volumes {
+                for member in members {
+                    let device = &self.topology.devices[member];
+                    let member_volume = self.member_volume(group, device, level);
+                    state_changed |= self.state.devices.insert(device.stable_id(), member_volume)
+                        != Some(member_volume);
+                }
+            }
+            let snapped = group_config.dead_band(volume);
+            if copy && snapped != volume {
+                tracing::debug!("{group}: snapping {id} to {}", format::volume(snapped));
+                actions.push(Action::RestoreVolume {
+                    group: group.clone(),
+                    device: id,
+                    volume: snapped,
+                });
+            }
+            let mut copies = Vec::new();
+            let mut targets = Vec::new();
+            for to in members.iter().filter(|to| !copy || **to != id) {
+                let target = &self.topology.devices[to];
+                if group_config.direction(target) == Direction::Lead {
+                    continue;
+                }
+                if group_config.skip_suspended && target.suspended {
+                    tracing::debug!("{group}: skipping suspended {to}");
+                    continue;
+                }
+                // Devices with coarse steps are written rounded to a step rather than copied
+                if copy && !group_config.transforms() && target.volume_step().is_none() {
+                    copies.push(*to);
+                    targets.push(target.label());
+                    continue;
+                }
+                let member_volume = self.member_volume(group, target, level);
+                let delta = member_volume.abs_diff(target.volume);
+                if delta > 0 && delta < group_config.min_delta() {
+                    self.counters.syncs_skipped += 1;
+                } else if delta > 0 {
+                    if !copy {
+                        self.expected.insert(*to, member_volume);
+                    }
+                    targets.push(target.label());
+                    actions.push(Action::RestoreVolume {
+                        group: group.clone(),
+                        device: *to,
+                        volume: member_volume,
+                    });
+                }
+            }
+            if !targets.is_empty() {
+                tracing::info!(
+                    "{group}: {} -> {} at {}",
+                    self.topology.devices[&id].label(),
+                    targets.join(", "),
+                    format::volume(level)
+                );
+            }
+            if !copies.is_empty() {
+                actions.push(Action::SyncVolume {
+                    group: group.clone(),
+                    from: id,
+                    to: copies,
+                    min_delta: group_config.min_delta(),
+                });
+            }
+        }
+        if state_changed {
+            actions.push(Action::SaveState(self.state.clone()));
+        }
+        actions
+    }
+
+    /// The group level computed by `strategy` from every member that isn't a follower
+    fn aggregate(&self, group: &str, strategy: Strategy, members: &BTreeSet<DeviceId>) -> u32 {
+        let group_config = &self.groups[group];
+        let levels: Vec<u32> = members
+            .iter()
+            .map(|id| &self.topology.devices[id])
+            .filter(|device| group_config.direction(device) != Direction::Follow)
+            .map(|device| self.group_level(group, device, device.volume))
+            .collect();
+        match strategy {
+            Strategy::Highest => levels.iter().copied().max().unwrap_or(0),
+            Strategy::Lowest => levels.iter().copied().min().unwrap_or(0),
+            Strategy::Average if !levels.is_empty() => {
+                let sum: u64 = levels.iter().map(|level| *level as u64).sum();
+                (sum / levels.len() as u64) as u32
+            }
+            Strategy::Copy | Strategy::Average => 0,
+        }
+    }
+
+    /// Identifies the active ports of a group's members, e.g. `serial:headphones,other:speaker`
+    fn port_key(&self, members: &BTreeSet<DeviceId>) -> String {
+        let ports: Vec<String> = members
+            .iter()
+            .map(|id| &self.topology.devices[id])
+            .filter_map(|device| {
+                let port = device.active_port.as_ref()?;
+                Some(format!("{}:{port}", device.stable_id()))
+            })
+            .collect();
+        ports.join(",")
+    }
+
+    fn device_removed(&mut self, id: DeviceId) {
+        if let Some(device) = self.topology.devices.remove(&id) {
+            tracing::info!("removed {id} {}", device.label());
+            self.generation += 1;
+        }
+        self.original.remove(&id);
+        self.expected.remove(&id);
+        for group_state in self.group_states.values_mut() {
+            group_state.members.remove(&id);
+        }
+    }
+
+    /// Whether any pause token expires, so the caller knows ticks matter
+    pub fn has_leases(&self) -> bool {
+        self.group_states
+            .values()
+            .any(|group_state| group_state.holds.values().any(Option::is_some))
+    }
+
+    /// Takes or renews the pause token of `owner` on `group`, optionally leased for `lease` seconds
+    fn pause(&mut self, group: String, owner: String, lease: Option<u64>) -> Reply {
+        let Some(group_state) = self.group_states.get_mut(&group) else {
+            return Err(format!("unknown group: {group}"));
+        };
+        let expires = match lease {
+            Some(seconds) => Some(
+                self.now
+                    .checked_add(seconds)
+                    .ok_or_else(|| format!("lease too long: {seconds}"))?,
+            ),
+            None => None,
+        };
+        tracing::info!(%group, %owner, ?lease, "paused");
+        group_state.holds.insert(owner, expires);
+        Ok(format!(
+            "{group}: paused by {} owners",
+            group_state.holds.len()
+        ))
+    }
+
+    /// Releases the pause token of `owner` on `group`, resuming it once no token is left
+    fn resume(&mut self, group: &str, owner: &str) -> Reply {
+        let Some(holds) = self
+            .group_states
+            .get_mut(group)
+            .map(|group_state| &mut group_state.holds)
+            .filter(|holds| !holds.is_empty())
+        else {
+            return Err(format!("{group} is not paused"));
+        };
+        if holds.remove(owner).is_none() {
+            return Err(format!("{owner} holds no pause token on {group}"));
+        }
+        tracing::info!(%group, %owner, "pause token released");
+        if holds.is_empty() {
+            tracing::info!(%group, "resumed");
+            return Ok(format!("{group}: resumed"));
+        }
+        Ok(format!("{group}: still paused by {} owners", holds.len()))
+    }
+
+    /// Name of a playing application stream matching the `pause_while` rules of `group`
+    fn pausing_stream(&self, group: &str) -> Option<&str> {
+        let rules = &self.groups.get(group)?.pause_while;
+        self.topology
+            .streams
+            .values()
+            .find(|stream| !stream.corked && rules.iter().any(|rule| rule.matches(stream)))
+            .map(|stream| stream.name.as_str())
+    }
+
+    /// Whether `group` holds a pause token or is paused by an application stream
+    fn is_paused(&self, group: &str) -> bool {
+        self.group_states
+            .get(group)
+            .is_some_and(|group_state| !group_state.holds.is_empty())
+            || self.pausing_stream(group).is_some()
+    }
+
+    /// Groups paused by an application stream, with the name of the application
+    fn app_pauses(&self) -> BTreeMap<String, String> {
+        self.groups
+            .keys()
+            .filter_map(|group| {
+                let app = self.pausing_stream(group)?;
+                Some((group.clone(), app.to_string()))
+            })
+            .collect()
+    }
+
+    fn log_app_pauses(&self, before: BTreeMap<String, String>) {
+        let after = self.app_pauses();
+        for (group, app) in &after {
+            if !before.contains_key(group) {
+                tracing::info!(%group, %app, "paused while application plays");
+            }
+        }
+        for group in before.keys() {
+            if !after.contains_key(group) {
+                tracing::info!(%group, "application stopped, resumed");
+            }
+        }
+    }
+
+    fn expire_pauses(&mut self) {
+        let now = self.now;
+        for (group, group_state) in &mut self.group_states {
+            let held = !group_state.holds.is_empty();
+            group_state.holds.retain(|owner, expires| {
+                let expired = expires.is_some_and(|expires| expires <= now);
+                if expired {
+                    tracing::info!(%group, %owner, "pause lease expired");
+                }
+                !expired
+            });
+            if held && group_state.holds.is_empty() {
+                tracing::info!(%group, "resumed");
+            }
+        }
+    }
+
+    /// Runs every step of a macro through the command layer, replying with the result of each step;
+    /// the macro fails if any step does, but later steps still run
+    fn run_macro(&mut self, name: &str) -> Vec<Action> {
+        let Some(steps) = self.macros.get(name).cloned() else {
+            return vec![Action::Reply(Err(format!("unknown macro: {name}")))];
+        };
+        let mut actions = Vec::new();
+        let mut results = Vec::new();
+        let mut failed = false;
+        for step in steps {
+            let reply = match Command::parse(&step) {
+                Ok(Command::Run(_)) => Err("macros can't run other macros".to_string()),
+                Ok(command) => {
+                    let mut reply = Ok(String::new());
+                    for action in self.handle_command(command) {
+                        match action {
+                            Action::Reply(r) => reply = r,
+                            action => actions.push(action),
+                        }
+                    }
+                    reply
+                }
+                Err(e) => Err(e),
+            };
+            failed |= reply.is_err();
+            results.push(match reply {
+                Ok(message) => format!("{step}: ok {message}"),
+                Err(message) => format!("{step}: error {message}"),
+            });
+        }
+        let results = results.join("\n");
+        actions.push(Action::Reply(if failed {
+            Err(results)
+        } else {
+            Ok(results)
+        }));
+        actions
+    }
+
+    fn handle_command(&mut self, command: Command) -> Vec<Action> {
+        match command {
+            Command::SetVolume { group, volume }
+                if self.groups.get(&group).is_some_and(GroupConfig::transforms) =>
+            {
+                // Every member is set through its own offset, translation and dead-bands, so the
+                // level changes rather than a volume
+                let Some(group_state) = self.group_states.get_mut(&group) else {
+                    return vec![Action::Reply(Err(format!("unknown group: {group}")))];
+                };
+                let Some(level) = group_state.level else {
+                    return vec![Action::Reply(Err(format!("{group} has no level yet")))];
+                };
+                group_state.level = Some(volume.apply(level));
+                let mut actions = self.realign(&group);
+                actions.push(Action::Reply(Ok(format!("{group}: {volume}"))));
+                actions
+            }
+            Command::SetVolume { group, volume } => match self.group_states.get(&group) {
+                Some(GroupState { members, .. }) => {
+                    let skip_suspended = self.groups[&group].skip_suspended;
+                    let mut devices: Vec<DeviceId> = members
+                        .iter()
+                        .filter(|id| !(skip_suspended && self.topology.devices[id].suspended))
+                        .copied()
+                        .collect();
+                    if devices.is_empty() {
+                        devices = members.iter().copied().collect();
+                    }
+                    vec![
+                        Action::SetVolume {
+                            group: group.clone(),
+                            devices,
+                            volume,
+                        },
+                        Action::Reply(Ok(format!("{group}: {volume}"))),
+                    ]
+                }
+                None => vec![Action::Reply(Err(format!("unknown group: {group}")))],
+            },
+            Command::Status => vec![Action::Reply(Ok(self.status()))],
+            Command::Snapshot(group) => vec![Action::Reply(self.snapshot_json(group.as_deref()))],
+            Command::ListSinks => vec![Action::Reply(Ok(self.list_sinks()))],
+            Command::Doctor => vec![Action::Reply(Ok(self.doctor()))],
+            Command::SaveProfile(name) => self.save_profile(name),
+            Command::LoadProfile(name) => self.load_profile(&name),
+            Command::Resolve {
+                pattern,
+                kind,
+                json,
+            } => vec![Action::Reply(self.resolve(&pattern, kind, json))],
+            Command::Explain {
+                group,
+                device,
+                volume,
+            } => vec![Action::Reply(self.explain(&group, &device, volume))],
+            Command::Run(name) => self.run_macro(&name),
+            Command::Resync => {
+                let groups: Vec<&String> = self
+                    .group_states
+                    .keys()
+                    .filter(|group| !self.is_paused(group))
+                    .collect();
+                let mut actions: Vec<Action> = groups
+                    .iter()
+                    .flat_map(|group| self.realign(group))
+                    .collect();
+                actions.push(Action::Reply(Ok(format!(
+                    "resynced {} groups",
+                    groups.len()
+                ))));
+                actions
+            }
+            Command::Calibrate(group) => self.calibrate(&group),
+            Command::History { json } => vec![Action::Reply(Ok(self.history_reply(json)))],
+            Command::SetLogLevel(directives) => vec![Action::SetLogFilter(directives)],
+            Command::Dump(format) => vec![Action::Reply(Ok(self.graph().render(format)))],
+            Command::Pause {
+                group,
+                owner,
+                lease,
+            } => vec![Action::Reply(self.pause(group, owner, lease))],
+            Command::Resume { group, owner } => vec![Action::Reply(self.resume(&group, &owner))],
+            Command::ListProfiles => {
+                let names: Vec<&str> = self.state.profiles.keys().map(|n| n.as_str()).collect();
+                vec![Action::Reply(Ok(names.join("\n")))]
+            }
+        }
+    }
+
+    /// Lists the devices of `kind`, or of any kind, whose name matches `pattern`, as the group
+    /// matcher sees them
+    fn resolve(&self, pattern: &str, kind: Option<DeviceKind>, json: bool) -> Reply {
+        let matches = self
+            .topology
+            .devices
+            .values()
+            .filter(|device| kind.is_none_or(|kind| device.kind == kind))
+            .filter(|device| matcher::glob(pattern, &device.name));
+        if json {
+            let devices: Vec<serde_json::Value> = matches
+                .map(|device| {
+                    serde_json::json!({
+                        "kind": device.kind,
+                        "index": device.index,
+                        "name": device.name,
+                        "description": device.description(),
+                        "volume": device.volume,
+                        "seat": device.seat,
+                    })
+                })
+                .collect();
+            return serde_json::to_string(&devices).map_err(|e| e.to_string());
+        }
+        let lines: Vec<String> = matches
+            .map(|device| {
+                format!(
+                    "{}\t{}\t{}\t{}",
+                    device.id(),
+                    device.name,
+                    format::volume(device.volume),
+                    device.description().unwrap_or("")
+                )
+            })
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    /// Every group with its level and flags, linked to its members in the direction changes flow
+    fn graph(&self) -> Graph {
+        let mut graph = Graph::default();
+        let mut devices = BTreeSet::new();
+        for (group, group_state) in &self.group_states {
+            let members = &group_state.members;
+            let group_config = &self.groups[group];
+            let context = self.context_name(group);
+            let state = self.contexts.get(context).map_or("Unknown", |s| s.as_str());
+            let mut label = vec![group.clone()];
+            if let Some(level) = group_state.level {
+                label.push(format!("level {}", format::volume(level)));
+            }
+            let paused = self.is_paused(group);
+            if paused {
+                label.push("paused".to_string());
+            }
+            let degraded = state != "Ready";
+            if degraded {
+                label.push(format!("context {context}: {state}"));
+            }
+            let id = format!("group {group}");
+            graph.nodes.push(Node {
+                id: id.clone(),
+                label,
+                shape: Shape::Group,
+                flagged: paused || degraded,
+            });
+            for member in members {
+                let device = &self.topology.devices[member];
+                devices.insert(*member);
+                let flow = match group_config.direction(device) {
+                    Direction::Both => Flow::Both,
+                    Direction::Lead => Flow::Back,
+                    Direction::Follow => Flow::Forward,
+                };
+                graph.edges.push(Edge {
+                    from: id.clone(),
+                    to: member.to_string(),
+                    label: group_config
+                        .translation(&device.name)
+                        .map(|t| t.summary())
+                        .unwrap_or_default(),
+                    flow,
+                });
+            }
+        }
+        for id in devices {
+            let device = &self.topology.devices[&id];
+            let mut label = vec![device.name.clone(), format::volume(device.volume)];
+            if device.suspended {
+                label.push("suspended".to_string());
+            }
+            graph.nodes.push(Node {
+                id: id.to_string(),
+                label,
+                shape: Shape::Device,
+                flagged: device.suspended,
+            });
+        }
+        graph
+    }
+
+    /// Shows how a group level becomes the volume of member `device`, stage by stage
+    fn explain(&self, group: &str, device: &str, volume: u32) -> Reply {
+        let group_config = self
+            .groups
+            .get(group)
+            .ok_or_else(|| format!("unknown group: {group}"))?;
+        let level = percent_to_volume(volume);
+        let mut lines = vec![format!("level: {}", format::volume(level))];
+        match group_config.translation(device) {
+            Some(translation) => {
+                for (stage, volume) in translation.trace(&group_config.stages, level) {
+                    lines.push(format!("{stage}: {}", format::volume(volume)));
+                }
+            }
+            None => lines.push(format!("{device} has no translation")),
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Stores the current volume of every managed device under `name`
+    fn save_profile(&mut self, name: String) -> Vec<Action> {
+        let volumes: BTreeMap<String, u32> = self
+            .group_states
+            .values()
+            .flat_map(|group_state| &group_state.members)
+            .map(|id| &self.topology.devices[id])
+            .map(|device| (device.stable_id(), device.volume))
+            .collect();
+        let reply = format!("{name}: saved {} devices", volumes.len());
+        self.state.profiles.insert(name, volumes);
+        vec![
+            Action::SaveState(self.state.clone()),
+            Action::Reply(Ok(reply)),
+        ]
+    }
+
+    /// Sets every present device saved in profile `name` back to its saved volume
+    fn load_profile(&mut self, name: &str) -> Vec<Action> {
+        let Some(volumes) = self.state.profiles.get(name) else {
+            return vec![Action::Reply(Err(format!("unknown profile: {name}")))];
+        };
+        let mut actions = Vec::new();
+        let mut restored = BTreeSet::new();
+        let mut levels = Vec::new();
+        for (group, group_state) in &self.group_states {
+            for id in &group_state.members {
+                let device = &self.topology.devices[id];
+                let Some(volume) = volumes.get(&device.stable_id()) else {
+                    continue;
+                };
+                levels.push((group.clone(), self.group_level(group, device, *volume)));
+                if restored.insert(*id) {
+                    actions.push(Action::RestoreVolume {
+                        group: group.clone(),
+                        device: *id,
+                        volume: *volume,
+                    });
+                }
+            }
+        }
+        for (group, level) in levels {
+            if let Some(group_state) = self.group_states.get_mut(&group) {
+                group_state.level = Some(level);
+            }
+        }
+        actions.push(Action::Reply(Ok(format!(
+            "{name}: restored {} devices",
+            restored.len()
+        ))));
+        actions
+    }
+
+    fn context_name<'a>(&self, group: &'a str) -> &'a str {
+        match self.groups.get(group) {
+            Some(group_config) if group_config.isolated => group,
+            _ => SHARED_CONTEXT,
+        }
+    }
+
+    /// The state of `group` as it is now
+    pub fn snapshot(&self, group: &str) -> Option<Snapshot> {
+        let group_state = self.group_states.get(group)?;
+        let context = self.context_name(group);
+        let paused_by = self.pausing_stream(group).map(str::to_string);
+        let mode = if !group_state.holds.is_empty() || paused_by.is_some() {
+            Mode::Paused
+        } else if group_state.members.len() < 2 {
+            Mode::Idle
+        } else {
+            Mode::Syncing
+        };
+        Some(Snapshot {
+            version: SNAPSHOT_VERSION,
+            group: group.to_string(),
+            mode,
+            level: group_state.level,
+            muted: !group_state.members.is_empty()
+                && group_state
+                    .members
+                    .iter()
+                    .all(|id| self.topology.devices[id].mute),
+            members: group_state.members.iter().copied().collect(),
+            holds: group_state.holds.clone(),
+            paused_by,
+            context: context.to_string(),
+            context_state: self
+                .contexts
+                .get(context)
+                .map_or("Unknown".to_string(), String::clone),
+        })
+    }
+
+    /// Snapshots of every group
+    pub fn snapshots(&self) -> Vec<Snapshot> {
+        self.group_states
+            .keys()
+            .filter_map(|group| self.snapshot(group))
+            .collect()
+    }
+
+    /// Snapshots of `group` or every group as JSON
+    fn snapshot_json(&self, group: Option<&str>) -> Reply {
+        let json = match group {
+            Some(group) => {
+                let snapshot = self
+                    .snapshot(group)
+                    .ok_or_else(|| format!("unknown group: {group}"))?;
+                serde_json::to_string(&snapshot)
+            }
+            None => serde_json::to_string(&self.snapshots()),
+        };
+        json.map_err(|e| e.to_string())
+    }
+
+    fn status(&self) -> String {
+        let mut lines = Vec::new();
+        for snapshot in self.snapshots() {
+            let names: Vec<String> = snapshot
+                .members
+                .iter()
+                .map(|id| {
+                    let device = &self.topology.devices[id];
+                    format!(
+                        "{id} {} {} @{}Hz",
+                        device.display_name(),
+                        format::volume(device.volume),
+                        device.rate
+                    )
+                })
+                .collect();
+            let level = snapshot.level.map_or("unknown".to_string(), format::volume);
+            lines.push(format!(
+                "{}: level {level}, {} members [{}] (context {}: {})",
+                snapshot.group,
+                snapshot.members.len(),
+                names.join(", "),
+                snapshot.context,
+                snapshot.context_state
+            ));
+            if !snapshot.holds.is_empty() {
+                let holders: Vec<String> = snapshot
+                    .holds
+                    .iter()
+                    .map(|(owner, expires)| match expires {
+                        Some(expires) => {
+                            format!("{owner} ({}s left)", expires.saturating_sub(self.now))
+                        }
+                        None => owner.clone(),
+                    })
+                    .collect();
+                lines.push(format!("  paused by {}", holders.join(", ")));
+            }
+            if let Some(app) = &snapshot.paused_by {
+                lines.push(format!("  paused while {app} is playing"));
+            }
+        }
+        lines.push(format!("topology: {}", self.topology.summary()));
+        if let Some(watcher) = &self.watcher {
+            lines.push(format!("config watcher: {watcher}"));
+        }
+        if self.performance {
+            lines.push("performance mode: background work deferred".to_string());
+        }
+        lines.push(self.counters.to_string());
+        lines.join("\n")
+    }
+
+    fn list_sinks(&self) -> String {
+        let lines: Vec<String> = self
+            .topology
+            .devices
+            .values()
+            .filter(|device| device.kind == DeviceKind::Sink)
+            .map(|device| {
+                format!(
+                    "{}\t{}\t{}\t{}\t[{}]",
+                    device.id(),
+                    device.name,
+                    device.description().unwrap_or(""),
+                    device.sample_spec,
+                    device.formats.join(", ")
+                )
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    /// Members of `group` by sample rate, when they don't all share the same one
+    fn rate_mismatch(&self, members: &BTreeSet<DeviceId>) -> Option<String> {
+        let mut rates: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+        for id in members {
+            rates
+                .entry(self.topology.devices[id].rate)
+                .or_default()
+                .push(id.to_string());
+        }
+        if rates.len() < 2 {
+            return None;
+        }
+        let rates: Vec<String> = rates
+            .iter()
+            .map(|(rate, ids)| format!("{rate}Hz: {}", ids.join(", ")))
+            .collect();
+        Some(rates.join("; "))
+    }
+
+    /// Findings of every check, each prefixed by the rule that found it
+    fn doctor(&self) -> String {
+        let mut findings = Vec::new();
+        for (group, group_state) in &self.group_states {
+            let members = &group_state.members;
+            if members.len() < 2 {
+                findings.push(format!(
+                    "single-member: {group} has {} members, nothing to sync",
+                    members.len()
+                ));
+            }
+            if let Some(rates) = self.rate_mismatch(members) {
+                findings.push(format!(
+                    "rate-mismatch: {group} members run at different sample rates ({rates}), \
+                     which can sound like a difference in loudness"
+                ));
+            }
+            for device in self.flat_volume_members(group) {
+                findings.push(format!(
+                    "flat-volume: {group} member {} moves with its loudest stream, set \
+                     flat_volume_follow = true to only sync to it",
+                    device.label()
+                ));
+            }
+        }
+        for finding in self.unmatched() {
+            findings.push(format!("unmatched: {finding}"));
+        }
+        for (context, state) in &self.contexts {
+            if state != "Ready" {
+                findings.push(format!("connection: context {context} is {state}"));
+            }
+        }
+        if findings.is_empty() {
+            return "no problems found".to_string();
+        }
+        findings.join("\n")
+    }
+
+    /// Name patterns of every group that match no device present, each with the closest name when
+    /// it looks like a typo
+    fn unmatched(&self) -> Vec<String> {
+        let mut findings = Vec::new();
+        for (group, group_config) in &self.groups {
+            for (kind, patterns) in [
+                (DeviceKind::Sink, &group_config.sinks),
+                (DeviceKind::Source, &group_config.sources),
+            ] {
+                let names: Vec<&str> = self
+                    .topology
+                    .devices
+                    .values()
+                    .filter(|device| device.kind == kind)
+                    .map(|device| device.name.as_str())
+                    .collect();
+                let kind = if kind == DeviceKind::Sink {
+                    "sink"
+                } else {
+                    "source"
+                };
+                for pattern in patterns {
+                    if names.iter().any(|name| matcher::glob(pattern, name)) {
+                        continue;
+                    }
+                    let mut finding = format!("{group}: {kind} \"{pattern}\" matches nothing");
+                    if let Some(name) = matcher::suggest(pattern, names.iter().copied()) {
+                        finding.push_str(&format!(", did you mean \"{name}\"?"));
+                    }
+                    findings.push(finding);
+                }
+            }
+        }
+        findings
+    }
+
+    /// Warns about groups whose members have different sample rates, if configured
+    fn check_rates(&self) {
+        for (group, group_state) in &self.group_states {
+            if !self.groups[group].warn_rate_mismatch {
+                continue;
+            }
+            if let Some(rates) = self.rate_mismatch(&group_state.members) {
+                tracing::warn!("{group}: members run at different sample rates ({rates})");
+            }
+        }
+    }
+
+    /// Members of `group` with flat volumes that changes are still synced from
+    fn flat_volume_members(&self, group: &str) -> Vec<&SinkDetails> {
+        let (Some(group_config), Some(group_state)) =
+            (self.groups.get(group), self.group_states.get(group))
+        else {
+            return Vec::new();
+        };
+        if group_config.flat_volume_follow {
+            return Vec::new();
+        }
+        group_state
+            .members
+            .iter()
+            .filter_map(|id| self.topology.devices.get(id))
+            .filter(|device| device.flat_volume)
+            .collect()
+    }
+
+    /// Warns once about every member with flat volumes, where a stream turned up moves the sink
+    /// and with it the whole group
+    fn check_flat_volumes(&mut self) {
+        let mut warned = std::mem::take(&mut self.flat_warned);
+        for group in self.group_states.keys() {
+            for device in self.flat_volume_members(group) {
+                if warned.insert(device.name.clone()) {
+                    tracing::warn!(
+                        "{group}: {} has flat volumes, turning up one of its streams turns up \
+                         the whole group; set flat_volume_follow = true to only sync to it",
+                        device.label()
+                    );
+                }
+            }
+        }
+        self.flat_warned = warned;
+    }
+
+    /// Devices known at the current generation, for a reload to match against off the event loop
+    pub fn devices(&self) -> (u64, Vec<SinkDetails>) {
+        (
+            self.generation,
+            self.topology.devices.values().cloned().collect(),
+        )
+    }
+
+    /// Members of every group
+    pub fn members(&self) -> Members {
+        self.group_states
+            .iter()
+            .map(|(group, group_state)| (group.clone(), group_state.members.clone()))
+            .collect()
+    }
+
+    fn update_members(&mut self) {
+        let members = group::members(&self.groups, self.topology.devices.values());
+        self.set_members(members);
+    }
+
+    /// Makes `members` the members of every group, starting groups without a level at the level
+    /// of their first member
+    fn set_members(&mut self, members: Members) {
+        self.group_states
+            .retain(|group, _| self.groups.contains_key(group));
+        for (group, members) in members {
+            let first_level = members
+                .first()
+                .and_then(|id| self.topology.devices.get(id))
+                .map(|device| self.group_level(&group, device, device.volume));
+            let group_state = self.group_states.entry(group).or_default();
+            group_state.members = members;
+            if let Some(level) = first_level {
+                group_state.level.get_or_insert(level);
+            }
+        }
+    }
+
+    /// Offset of `device` from the level of `group`: 0 unless the group is relative, and `None`
+    /// when it is yet to be calibrated
+    fn offset(&self, group: &str, device: &SinkDetails) -> Option<i64> {
+        if self.groups.get(group)?.mode != SyncMode::Relative {
+            return Some(0);
+        }
+        self.state
+            .offsets
+            .get(group)
+            .and_then(|offsets| offsets.get(&device.stable_id()))
+            .copied()
+    }
+
+    /// The volume of `device` at the group `level`, translated, moved by its offset and rounded to
+    /// its volume steps
+    fn member_volume(&self, group: &str, device: &SinkDetails, level: u32) -> u32 {
+        let volume = self.groups[group].member_volume(&device.name, level);
+        device.round_to_step(apply_offset(
+            volume,
+            self.offset(group, device).unwrap_or(0),
+        ))
+    }
+
+    /// The group level that `device` at `volume` corresponds to
+    fn group_level(&self, group: &str, device: &SinkDetails, volume: u32) -> u32 {
+        let volume = apply_offset(volume, -self.offset(group, device).unwrap_or(0));
+        self.groups[group].group_level(&device.name, volume)
+    }
+
+    /// Records the offset from the group level of every member of a relative group that has none
+    /// yet, returning whether any was recorded
+    fn capture_offsets(&mut self) -> bool {
+        let mut captured = Vec::new();
+        for (group, group_state) in &self.group_states {
+            let Some(level) = group_state.level else {
+                continue;
+            };
+            for id in &group_state.members {
+                let Some(device) = self.topology.devices.get(id) else {
+                    continue;
+                };
+                if self.offset(group, device).is_some() {
+                    continue;
+                }
+                let volume = self.groups[group].member_volume(&device.name, level);
+                let offset = device.volume as i64 - volume as i64;
+                tracing::info!(
+                    "{group}: keeping {} at {} from the group level",
+                    device.label(),
+                    format::offset(offset)
+                );
+                captured.push((group.clone(), device.stable_id(), offset));
+            }
+        }
+        let changed = !captured.is_empty();
+        for (group, id, offset) in captured {
+            self.state
+                .offsets
+                .entry(group)
+                .or_default()
+                .insert(id, offset);
+        }
+        changed
+    }
+
+    /// The recorded volume changes, oldest first, as lines or a JSON array
+    fn history_reply(&self, json: bool) -> String {
+        if json {
+            let changes: Vec<&Change> = self.history.iter().collect();
+            return serde_json::to_string(&changes).expect("failed to serialize history");
+        }
+        let lines: Vec<String> = self.history.iter().map(Change::to_string).collect();
+        if lines.is_empty() {
+            return "no volume changes recorded".to_string();
+        }
+        lines.join("\n")
+    }
+
+    /// Forgets the offsets of a relative group and records the current ones
+    fn calibrate(&mut self, group: &str) -> Vec<Action> {
+        let Some(group_config) = self.groups.get(group) else {
+            return vec![Action::Reply(Err(format!("unknown group: {group}")))];
+        };
+        if group_config.mode != SyncMode::Relative {
+            return vec![Action::Reply(Err(format!("{group} is not relative")))];
+        }
+        self.state.offsets.remove(group);
+        self.capture_offsets();
+        let count = self.state.offsets.get(group).map_or(0, BTreeMap::len);
+        vec![
+            Action::SaveState(self.state.clone()),
+            Action::Reply(Ok(format!("{group}: calibrated {count} members"))),
+        ]
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    const DESK: &str = r#"
+        [groups.desk]
+        sinks = ["speakers", "headphones", "dac"]
+    "#;
+
+    pub(crate) fn sink(index: u32, name: &str, percent: u32) -> SinkDetails {
+        SinkDetails {
+            index,
+            name: name.to_string(),
+            description: None,
+            card_name: None,
+            kind: DeviceKind::Sink,
+            properties: BTreeMap::new(),
+            volume: percent_to_volume(percent),
+            mute: false,
+            active_port: None,
+            ports: Vec::new(),
+            card: None,
+            suspended: false,
+            sample_spec: String::new(),
+            rate: 0,
+            formats: Vec::new(),
+            seat: None,
+            monitor: None,
+            flat_volume: false,
+            hw_volume: false,
+            volume_steps: 0,
+        }
+    }
+
+    /// An engine running `config` that has listed `sinks`
+    fn engine(config: &str, sinks: Vec<SinkDetails>) -> Engine {
+        let config: Config = toml::from_str(config).expect("invalid test config");
+        let mut engine = Engine::new();
+        engine.handle(Input::ConfigLoaded(Box::new(config)));
+        engine.handle(Input::SinkList(sinks));
+        engine
+    }
+
+    /// The actions that write a volume
+    fn writes(actions: Vec<Action>) -> Vec<Action> {
+        actions
+            .into_iter()
+            .filter(|action| {
+                matches!(
+                    action,
+                    Action::SyncVolume { .. }
+                        | Action::SetVolume { .. }
+                        | Action::RestoreVolume { .. }
+                )
+            })
+            .collect()
+    }
+
+    fn replies(actions: &[Action]) -> Vec<Reply> {
+        actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::Reply(reply) => Some(reply.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn sync(from: u32, to: &[u32]) -> Action {
+        Action::SyncVolume {
+            group: "desk".to_string(),
+            from: DeviceId::sink(from),
+            to: to.iter().copied().map(DeviceId::sink).collect(),
+            min_delta: 0,
+        }
+    }
+
+    fn restore(index: u32, volume: u32) -> Action {
+        Action::RestoreVolume {
+            group: "desk".to_string(),
+            device: DeviceId::sink(index),
+            volume,
+        }
+    }
+
+    fn desk() -> Vec<SinkDetails> {
+        vec![
+            sink(0, "speakers", 40),
+            sink(1, "headphones", 40),
+            sink(2, "dac", 40),
+        ]
+    }
+
+    #[test]
+    fn change_is_copied_to_the_other_members() {
+        let mut engine = engine(DESK, desk());
+        let actions = engine.handle(Input::SinkChanged(sink(1, "headphones", 60)));
+        assert_eq!(writes(actions), vec![sync(1, &[0, 2])]);
+    }
+
+    #[test]
+    fn arbitration_picks_one_change_per_group() {
+        let changes = vec![
+            sink(1, "headphones", 50),
+            sink(0, "speakers", 70),
+            sink(2, "dac", 60),
+        ];
+        let cases = [
+            ("last_writer", &[][..], sync(2, &[0, 1])),
+            ("highest", &[][..], sync(0, &[1, 2])),
+            ("priority", &["headphones", "dac"][..], sync(1, &[0, 2])),
+        ];
+        for (arbitration, priority, expected) in cases {
+            let config =
+                format!("{DESK}\narbitration = \"{arbitration}\"\npriority = {priority:?}\n");
+            let mut engine = engine(&config, desk());
+            let actions = engine.handle(Input::Changes(changes.clone()));
+            assert_eq!(writes(actions), vec![expected], "{arbitration}");
+        }
+    }
+
+    #[test]
+    fn lease_pauses_until_it_expires() {
+        let mut engine = engine(DESK, desk());
+        engine.handle(Input::Tick(100));
+        let pause = Command::Pause {
+            group: "desk".to_string(),
+            owner: "game".to_string(),
+            lease: Some(10),
+        };
+        let actions = engine.handle(Input::Command(pause));
+        assert_eq!(
+            replies(&actions),
+            vec![Ok("desk: paused by 1 owners".to_string())]
+        );
+        assert!(engine.has_leases());
+
+        let actions = engine.handle(Input::SinkChanged(sink(0, "speakers", 60)));
+        assert_eq!(writes(actions), Vec::new());
+        engine.handle(Input::Tick(109));
+        assert!(engine.has_leases());
+        engine.handle(Input::Tick(110));
+        assert!(!engine.has_leases());
+
+        let actions = engine.handle(Input::SinkChanged(sink(0, "speakers", 70)));
+        assert_eq!(writes(actions), vec![sync(0, &[1, 2])]);
+    }
+
+    #[test]
+    fn profile_restores_saved_volumes() {
+        let sinks = vec![sink(0, "speakers", 40), sink(1, "headphones", 30)];
+        let mut engine = engine(DESK, sinks);
+        let actions = engine.handle(Input::Command(Command::SaveProfile("night".to_string())));
+        assert_eq!(
+            replies(&actions),
+            vec![Ok("night: saved 2 devices".to_string())]
+        );
+        engine.handle(Input::SinkChanged(sink(0, "speakers", 80)));
+
+        let actions = engine.handle(Input::Command(Command::LoadProfile("night".to_string())));
+        assert_eq!(
+            replies(&actions),
+            vec![Ok("night: restored 2 devices".to_string())]
+        );
+        assert_eq!(
+            writes(actions),
+            vec![
+                restore(0, percent_to_volume(40)),
+                restore(1, percent_to_volume(30))
+            ]
+        );
+        assert_eq!(
+            engine.snapshot("desk").and_then(|s| s.level),
+            Some(percent_to_volume(30))
+        );
+        let actions = engine.handle(Input::Command(Command::LoadProfile("day".to_string())));
+        assert_eq!(
+            replies(&actions),
+            vec![Err("unknown profile: day".to_string())]
+        );
+    }
+
+    #[test]
+    fn coarse_steps_settle_instead_of_bouncing() {
+        let coarse = |percent| SinkDetails {
+            hw_volume: true,
+            volume_steps: 11,
+            ..sink(2, "dac", percent)
+        };
+        let mut engine = engine(
+            DESK,
+            vec![
+                sink(0, "speakers", 40),
+                sink(1, "headphones", 40),
+                coarse(40),
+            ],
+        );
+        let step = coarse(0).volume_step().unwrap();
+
+        let actions = engine.handle(Input::SinkChanged(sink(0, "speakers", 43)));
+        let stepped = coarse(0).round_to_step(percent_to_volume(43));
+        assert_eq!(stepped % step, 0);
+        assert_eq!(writes(actions), vec![restore(2, stepped), sync(0, &[1])]);
+
+        // The device lands on its step, or a little off it, which isn't a change of its own
+        for volume in [stepped, stepped + step / 2, stepped - step / 2] {
+            let settled = SinkDetails {
+                volume,
+                ..coarse(0)
+            };
+            let actions = engine.handle(Input::SinkChanged(settled));
+            assert_eq!(writes(actions), Vec::new(), "{volume}");
+        }
+
+        // A move of a whole step still is
+        let moved = SinkDetails {
+            volume: stepped + step,
+            ..coarse(0)
+        };
+        let actions = engine.handle(Input::SinkChanged(moved));
+        assert_eq!(writes(actions), vec![sync(2, &[0, 1])]);
+    }
+
+    #[test]
+    fn set_goes_through_the_translation() {
+        let config = format!("{DESK}\n[groups.desk.translate.headphones]\noffset = -10\n");
+        let sinks = vec![sink(0, "speakers", 40), sink(1, "headphones", 30)];
+        let mut engine = engine(&config, sinks);
+        let set = Command::SetVolume {
+            group: "desk".to_string(),
+            volume: VolumeSpec::Absolute(50),
+        };
+        let actions = engine.handle(Input::Command(set));
+        assert_eq!(replies(&actions), vec![Ok("desk: 50%".to_string())]);
+        assert_eq!(
+            writes(actions),
+            vec![
+                restore(0, percent_to_volume(50)),
+                restore(1, percent_to_volume(50) - percent_to_volume(10))
+            ]
+        );
+        assert_eq!(
+            engine.snapshot("desk").and_then(|s| s.level),
+            Some(percent_to_volume(50))
+        );
+    }
+
+    #[test]
+    fn profile_level_is_translated_back() {
+        let config = format!("{DESK}\n[groups.desk.translate.headphones]\noffset = -10\n");
+        let sinks = vec![sink(1, "headphones", 30)];
+        let mut engine = engine(&config, sinks);
+        engine.handle(Input::Command(Command::SaveProfile("night".to_string())));
+        engine.handle(Input::SinkChanged(sink(1, "headphones", 60)));
+
+        engine.handle(Input::Command(Command::LoadProfile("night".to_string())));
+        assert_eq!(
+            engine.snapshot("desk").and_then(|s| s.level),
+            Some(percent_to_volume(30) + percent_to_volume(10))
+        );
+    }
+
+    #[test]
+    fn reload_matches_again_when_devices_changed() {
+        let mut engine = engine(DESK, vec![sink(0, "speakers", 40)]);
+        let config: Config = toml::from_str(DESK).expect("invalid test config");
+        let (generation, devices) = engine.devices();
+        let members = group::members(&config.groups(), devices.iter());
+        let reload = || Input::ConfigReloaded {
+            config: Box::new(config.clone()),
+            members: members.clone(),
+            generation,
+        };
+
+        engine.handle(reload());
+        assert_eq!(engine.members(), members);
+
+        engine.handle(Input::SinkNew(sink(1, "headphones", 40)));
+        engine.handle(reload());
+        let desk = BTreeSet::from([DeviceId::sink(0), DeviceId::sink(1)]);
+        assert_eq!(engine.members()["desk"], desk);
+
+        let actions = engine.handle(Input::SinkChanged(sink(0, "speakers", 60)));
+        assert_eq!(writes(actions), vec![sync(0, &[1])]);
+    }
+
+    #[test]
+    fn translation_applies_both_ways() {
+        let config = format!("{DESK}\n[groups.desk.translate.headphones]\noffset = -10\n");
+        let sinks = vec![sink(0, "speakers", 40), sink(1, "headphones", 30)];
+        let mut engine = engine(&config, sinks);
+
+        let actions = engine.handle(Input::SinkChanged(sink(0, "speakers", 50)));
+        let headphones = percent_to_volume(50) - percent_to_volume(10);
+        assert_eq!(writes(actions), vec![restore(1, headphones)]);
+
+        let actions = engine.handle(Input::SinkChanged(sink(1, "headphones", 20)));
+        assert_eq!(writes(actions), vec![restore(0, percent_to_volume(30))]);
+        assert_eq!(
+            engine.snapshot("desk").and_then(|s| s.level),
+            Some(percent_to_volume(30))
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use std::sync::mpsc::Sender;
+
+use crate::command::{Command, Reply};
+use crate::config::Config;
+use crate::group::Members;
+use crate::sink::SinkDetails;
+use crate::topology::{CardDetails, StreamDetails};
+
+#[derive(Debug)]
+pub enum VolumeSyncEvent {
+    SinkNew(SinkDetails),
+    SinkChanged(SinkDetails),
+    SinkRemoved(u32),
+    SourceNew(SinkDetails),
+    SourceChanged(SinkDetails),
+    SourceRemoved(u32),
+    RecordingNew(SinkDetails),
+    RecordingChanged(SinkDetails),
+    RecordingRemoved(u32),
+    CardChanged(CardDetails),
+    CardRemoved(u32),
+    StreamChanged(StreamDetails),
+    StreamRemoved(u32),
+    /// Full listings requested from the server, delivered once complete
+    CardListLoaded(Vec<CardDetails>),
+    SinkListLoaded(Vec<SinkDetails>),
+    SourceListLoaded(Vec<SinkDetails>),
+    RecordingListLoaded(Vec<SinkDetails>),
+    StreamListLoaded(Vec<StreamDetails>),
+    /// Time to list everything again and check the topology model against it
+    Refresh,
+    Resumed,
+    IdlePoll,
+    /// A game or other performance sensitive work started or stopped
+    Performance(bool),
+    /// Once a second, for leases to expire
+    Tick,
+    ConfigChanged,
+    /// A config read off the event loop, ready to be applied with the members of its groups among
+    /// the devices known at `generation`
+    ConfigReady {
+        config: Box<Config>,
+        members: Members,
+        generation: u64,
+    },
+    /// A reloaded config that was invalid, the current one is kept
+    ConfigRejected,
+    WatcherError(String),
+    Command(Command, Sender<Reply>),
+    ContextState(String, String),
+    /// The MQTT broker connected again and has to be sent every group anew
+    Republish,
+    Shutdown,
+}
+
+/// Name of the context shared by every group that isn't isolated
+pub const SHARED_CONTEXT: &str = "shared";
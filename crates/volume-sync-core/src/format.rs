@@ -0,0 +1,59 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::volume::{VOLUME_NORM, VOLUME_UI_MAX};
+
+/// What 100% stands for when showing a volume
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reference {
+    /// Full volume without software amplification, `PA_VOLUME_NORM`
+    #[default]
+    Norm,
+    /// The most mixers allow with overdrive, `PA_VOLUME_UI_MAX`
+    Max,
+}
+
+impl Reference {
+    fn volume(&self) -> u32 {
+        match self {
+            Reference::Norm => VOLUME_NORM,
+            Reference::Max => VOLUME_UI_MAX,
+        }
+    }
+}
+
+/// Whether volumes are shown relative to `Reference::Max`, shared by every output path
+static RELATIVE_TO_MAX: AtomicBool = AtomicBool::new(false);
+
+pub fn set_reference(reference: Reference) {
+    RELATIVE_TO_MAX.store(reference == Reference::Max, Ordering::Relaxed);
+}
+
+pub fn reference() -> Reference {
+    if RELATIVE_TO_MAX.load(Ordering::Relaxed) {
+        Reference::Max
+    } else {
+        Reference::Norm
+    }
+}
+
+/// Formats a difference between volumes as a signed percentage of the reference, e.g. `+10%`
+pub fn offset(offset: i64) -> String {
+    let reference = reference().volume() as i64;
+    let percent = (offset * 100 + offset.signum() * reference / 2) / reference;
+    format!("{percent:+}%")
+}
+
+/// Formats a volume as a rounded percentage of the reference, e.g. `40%`; volumes amplified past
+/// full volume are marked, e.g. `120% (overdrive)`
+pub fn volume(volume: u32) -> String {
+    let reference = reference().volume() as u64;
+    let percent = (volume as u64 * 100 + reference / 2) / reference;
+    if volume > VOLUME_NORM {
+        format!("{percent}% (overdrive)")
+    } else {
+        format!("{percent}%")
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Text formats a graph can be rendered in
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            _ => Err(format!(
+                "unknown graph format: {s}, expected dot or mermaid"
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Group,
+    Device,
+}
+
+pub struct Node {
+    pub id: String,
+    pub label: Vec<String>,
+    pub shape: Shape,
+    /// Drawn highlighted, e.g. a paused group or a suspended device
+    pub flagged: bool,
+}
+
+/// Which ends of an edge volume changes flow to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flow {
+    Forward,
+    Back,
+    Both,
+}
+
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub label: String,
+    pub flow: Flow,
+}
+
+/// A graph of groups and devices, rendered for Graphviz or Mermaid
+#[derive(Default)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// Quotes text for a DOT string, escaping quotes and backslashes
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Node ids in Mermaid may only contain word characters
+fn mermaid_id(id: &str) -> String {
+    id.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Quotes text for a Mermaid label, which has no escapes for quotes
+fn mermaid_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "#quot;"))
+}
+
+impl Graph {
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.dot(),
+            GraphFormat::Mermaid => self.mermaid(),
+        }
+    }
+
+    fn dot(&self) -> String {
+        let mut out = String::from("digraph volume_sync {\n  rankdir=LR;\n");
+        for node in &self.nodes {
+            let shape = match node.shape {
+                Shape::Group => "box",
+                Shape::Device => "ellipse",
+            };
+            let style = if node.flagged {
+                ", style=filled, fillcolor=orange"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                out,
+                "  {} [label={}, shape={shape}{style}];",
+                dot_quote(&node.id),
+                dot_quote(&node.label.join("\n"))
+            );
+        }
+        for edge in &self.edges {
+            let dir = match edge.flow {
+                Flow::Forward => "forward",
+                Flow::Back => "back",
+                Flow::Both => "both",
+            };
+            let _ = writeln!(
+                out,
+                "  {} -> {} [label={}, dir={dir}];",
+                dot_quote(&edge.from),
+                dot_quote(&edge.to),
+                dot_quote(&edge.label)
+            );
+        }
+        out.push('}');
+        out
+    }
+
+    fn mermaid(&self) -> String {
+        let mut out = String::from("flowchart LR\n");
+        for node in &self.nodes {
+            let label = mermaid_quote(&node.label.join("<br>"));
+            let id = mermaid_id(&node.id);
+            let _ = match node.shape {
+                Shape::Group => writeln!(out, "  {id}[{label}]"),
+                Shape::Device => writeln!(out, "  {id}({label})"),
+            };
+            if node.flagged {
+                let _ = writeln!(out, "  style {id} fill:orange");
+            }
+        }
+        for edge in &self.edges {
+            let (from, to) = match edge.flow {
+                Flow::Back => (&edge.to, &edge.from),
+                Flow::Forward | Flow::Both => (&edge.from, &edge.to),
+            };
+            let arrow = match edge.flow {
+                Flow::Both => "<-->",
+                Flow::Forward | Flow::Back => "-->",
+            };
+            let label = if edge.label.is_empty() {
+                String::new()
+            } else {
+                format!("|{}|", mermaid_quote(&edge.label))
+            };
+            let _ = writeln!(
+                out,
+                "  {} {arrow}{label} {}",
+                mermaid_id(from),
+                mermaid_id(to)
+            );
+        }
+        out.trim_end().to_string()
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::GroupConfig;
+use crate::sink::{DeviceId, SinkDetails};
+
+/// Version of the `Snapshot` layout, bumped whenever a field changes meaning or goes away
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// What a group is doing right now
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Fewer than two members present, so there is nothing to sync
+    Idle,
+    Syncing,
+    /// Held by pause tokens or a playing application
+    Paused,
+}
+
+/// Members of every group, by group name
+pub type Members = BTreeMap<String, BTreeSet<DeviceId>>;
+
+/// The devices matching each of `groups`, with an entry for every group
+pub fn members<'a>(
+    groups: &BTreeMap<String, GroupConfig>,
+    devices: impl Iterator<Item = &'a SinkDetails> + Clone,
+) -> Members {
+    groups
+        .iter()
+        .map(|(group, group_config)| {
+            let members = devices
+                .clone()
+                .filter(|device| group_config.matches(device))
+                .map(SinkDetails::id)
+                .collect();
+            (group.clone(), members)
+        })
+        .collect()
+}
+
+/// Runtime state of a group, everything the engine tracks about it besides its config
+#[derive(Clone, Debug, Default)]
+pub struct GroupState {
+    pub members: BTreeSet<DeviceId>,
+    /// Last level propagated to every member
+    pub level: Option<u32>,
+    /// Pause tokens by owner, with the time their lease expires
+    pub holds: BTreeMap<String, Option<u64>>,
+    /// Whether the saved level was applied since startup
+    pub restored: bool,
+}
+
+/// A copy of a group's state at one point in time, for anything outside the engine to read
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub group: String,
+    pub mode: Mode,
+    pub level: Option<u32>,
+    /// Whether every member present is muted
+    #[serde(default)]
+    pub muted: bool,
+    pub members: Vec<DeviceId>,
+    pub holds: BTreeMap<String, Option<u64>>,
+    /// Application whose stream keeps the group paused
+    pub paused_by: Option<String>,
+    /// Connection the group writes through and its state
+    pub context: String,
+    pub context_state: String,
+}
@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::format;
+
+/// What made a device's volume change
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// Changed outside of volume-sync, e.g. by a mixer or a key
+    External,
+    /// Written by volume-sync to follow another member or the group level
+    Sync,
+    /// Written by volume-sync while setting up devices from a listing or the config
+    Startup,
+    /// Written by volume-sync for a command, e.g. `set`
+    Command,
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Trigger::External => "external",
+            Trigger::Sync => "sync",
+            Trigger::Startup => "startup",
+            Trigger::Command => "command",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One volume change of a device
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Change {
+    /// Seconds since the epoch
+    pub time: u64,
+    pub group: Option<String>,
+    pub device: String,
+    pub old: u32,
+    pub new: u32,
+    pub trigger: Trigger,
+}
+
+/// Formats seconds since the epoch as a UTC date and time, e.g. `2024-03-01 03:00:12`
+fn timestamp(time: u64) -> String {
+    let (days, seconds) = (time / 86400, time % 86400);
+    // Days to a civil date, after Howard Hinnant's `civil_from_days`
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ", timestamp(self.time))?;
+        if let Some(group) = &self.group {
+            write!(f, "{group} ")?;
+        }
+        write!(
+            f,
+            "{} {} -> {} ({})",
+            self.device,
+            format::volume(self.old),
+            format::volume(self.new),
+            self.trigger
+        )
+    }
+}
+
+/// The latest volume changes, oldest first, dropping the oldest past `capacity`
+#[derive(Debug, Default)]
+pub struct History {
+    changes: VecDeque<Change>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.changes.len() > capacity {
+            self.changes.pop_front();
+        }
+    }
+
+    pub fn push(&mut self, change: Change) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.changes.len() == self.capacity {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(change);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Change> {
+        self.changes.iter()
+    }
+}
+
+/// Appends `changes` to `path`, one JSON document per line
+pub fn append(path: &str, changes: &[Change]) -> io::Result<()> {
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    for change in changes {
+        let line = serde_json::to_string(change).expect("failed to serialize history entry");
+        writeln!(file, "{line}")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+
+use crate::engine::Input;
+
+/// Append-only record of engine inputs, one JSON document per line.
+#[derive(Default)]
+pub struct Journal {
+    path: Option<String>,
+    file: Option<File>,
+}
+
+impl Journal {
+    pub fn new() -> Journal {
+        Journal::default()
+    }
+
+    pub fn set_path(&mut self, path: Option<String>) {
+        if self.path == path {
+            return;
+        }
+        self.file = path.as_ref().and_then(|p| {
+            match OpenOptions::new().create(true).append(true).open(p) {
+                Ok(file) => {
+                    tracing::info!("journaling events to {p}");
+                    Some(file)
+                }
+                Err(e) => {
+                    tracing::error!("failed to open journal {p}: {e}");
+                    None
+                }
+            }
+        });
+        self.path = path;
+    }
+
+    pub fn record(&mut self, input: &Input) {
+        if let Some(file) = &mut self.file {
+            let line = serde_json::to_string(input).expect("failed to serialize journal entry");
+            if let Err(e) = writeln!(file, "{line}") {
+                tracing::warn!("failed to write journal: {e}");
+            }
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if let Some(file) = &mut self.file {
+            if let Err(e) = file.sync_all() {
+                tracing::warn!("failed to flush journal: {e}");
+            }
+        }
+    }
+}
+
+pub fn read(path: &str) -> Result<Vec<Input>, String> {
+    let file = File::open(path).map_err(|e| format!("failed to open {path}: {e}"))?;
+    let mut inputs = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("failed to read {path}: {e}"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let input = serde_json::from_str(&line)
+            .map_err(|e| format!("{path}:{}: invalid entry: {e}", number + 1))?;
+        inputs.push(input);
+    }
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+    use crate::command::Command;
+    use crate::config::Config;
+    use crate::engine::tests::sink;
+    use crate::engine::{Action, Engine};
+    use crate::volume::VolumeSpec;
+
+    /// A journal file of its own for a test
+    fn path(name: &str) -> String {
+        let path = env::temp_dir().join(format!(
+            "volume-sync-journal-{}-{name}.jsonl",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    fn inputs() -> Vec<Input> {
+        let config: Config = toml::from_str(
+            r#"
+            [groups.desk]
+            sinks = ["speakers", "headphones"]
+            "#,
+        )
+        .expect("invalid test config");
+        vec![
+            Input::ConfigLoaded(Box::new(config)),
+            Input::SinkList(vec![sink(0, "speakers", 40), sink(1, "headphones", 40)]),
+            Input::Tick(100),
+            Input::SinkChanged(sink(1, "headphones", 55)),
+            Input::Changes(vec![sink(0, "speakers", 30), sink(1, "headphones", 60)]),
+            Input::Command(Command::SetVolume {
+                group: "desk".to_string(),
+                volume: VolumeSpec::Increase(5),
+            }),
+            Input::SinkRemoved(1),
+            Input::SinkNew(sink(2, "headphones", 20)),
+            Input::Shutdown,
+        ]
+    }
+
+    fn run(inputs: Vec<Input>) -> Vec<Vec<Action>> {
+        let mut engine = Engine::new();
+        inputs
+            .into_iter()
+            .map(|input| engine.handle(input))
+            .collect()
+    }
+
+    fn record(path: &str, inputs: &[Input]) {
+        let mut journal = Journal::new();
+        journal.set_path(Some(path.to_string()));
+        for input in inputs {
+            journal.record(input);
+        }
+        journal.flush();
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = path("round-trip");
+        record(&path, &inputs());
+        let read = read(&path).expect("failed to read journal");
+        let _ = fs::remove_file(&path);
+        let json = |inputs: &[Input]| serde_json::to_string(inputs).expect("failed to serialize");
+        assert_eq!(json(&read), json(&inputs()));
+    }
+
+    #[test]
+    fn replay_produces_the_same_actions() {
+        let path = path("replay");
+        record(&path, &inputs());
+        let replayed = run(read(&path).expect("failed to read journal"));
+        let _ = fs::remove_file(&path);
+        let recorded = run(inputs());
+        assert!(recorded.iter().any(|actions| !actions.is_empty()));
+        assert_eq!(replayed, recorded);
+    }
+
+    #[test]
+    fn invalid_entry_names_its_line() {
+        let path = path("invalid");
+        fs::write(&path, "\"Shutdown\"\n\n{\"Tick\": \"soon\"}\n").expect("failed to write");
+        let result = read(&path);
+        let _ = fs::remove_file(&path);
+        let error = result.expect_err("invalid entry was read");
+        assert!(error.contains(":3: invalid entry"), "{error}");
+    }
+}
@@ -0,0 +1,19 @@
+pub mod backend;
+pub mod command;
+pub mod config;
+pub mod counters;
+pub mod digest;
+pub mod engine;
+pub mod event;
+pub mod format;
+pub mod graph;
+pub mod group;
+pub mod history;
+pub mod journal;
+pub mod matcher;
+pub mod sink;
+pub mod state;
+pub mod template;
+pub mod topology;
+pub mod translate;
+pub mod volume;
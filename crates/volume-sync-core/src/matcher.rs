@@ -0,0 +1,156 @@
+/// Matches `name` against a shell style pattern where `*` matches any run of characters and `?`
+/// matches exactly one; everything else must match literally.
+pub fn glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Number of single character insertions, deletions and substitutions turning `a` into `b`
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// The name closest to `pattern`, if it is close enough to be a typo of it
+pub fn suggest<'a>(pattern: &str, names: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (pattern.chars().count() / 4).max(2);
+    names
+        .into_iter()
+        .map(|name| (levenshtein(pattern, name), name))
+        .filter(|(distance, _)| *distance <= limit)
+        .min()
+        .map(|(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GroupConfig;
+    use crate::engine::tests::sink;
+
+    #[test]
+    fn wildcards() {
+        let cases = [
+            ("alsa_output.*", "alsa_output.usb-headset", true),
+            ("alsa_output.*", "alsa_output.", true),
+            ("alsa_output.*", "alsa_input.usb-mic", false),
+            ("*.analog-stereo", "alsa_output.pci.analog-stereo", true),
+            ("*.analog-stereo", "alsa_output.pci.analog-stereo.2", false),
+            ("*usb*", "alsa_output.usb-headset", true),
+            ("*", "", true),
+            ("", "", true),
+            ("", "desk", false),
+            ("card?", "card1", true),
+            ("card?", "card", false),
+            ("card?", "card12", false),
+            ("a*b*c", "aXbYbZc", true),
+            ("a*b*c", "aXbYc!", false),
+            ("**", "anything", true),
+            ("desk", "Desk", false),
+            ("h\u{e9}?d", "h\u{e9}ad", true),
+        ];
+        for (pattern, name, expected) in cases {
+            assert_eq!(glob(pattern, name), expected, "{pattern} against {name}");
+        }
+    }
+
+    #[test]
+    fn exclusions_win_over_matches() {
+        let group = GroupConfig {
+            sinks: vec!["alsa_output.*".to_string()],
+            exclude: vec!["*.hdmi-*".to_string(), "alsa_output.dock?".to_string()],
+            ..GroupConfig::default()
+        };
+        let cases = [
+            ("alsa_output.usb-headset", true),
+            ("alsa_output.pci.hdmi-stereo", false),
+            ("alsa_output.dock1", false),
+            ("alsa_output.dock12", true),
+            ("bluez_output.headphones", false),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(group.matches(&sink(0, name, 50)), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn edit_distance() {
+        let cases = [
+            ("", "", 0),
+            ("desk", "desk", 0),
+            ("", "desk", 4),
+            ("desk", "disk", 1),
+            ("desk", "dsk", 1),
+            ("desk", "desks", 1),
+            ("desk", "dsek", 2),
+            ("kitten", "sitting", 3),
+            ("h\u{e9}adset", "headset", 1),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(levenshtein(a, b), expected, "{a} to {b}");
+            assert_eq!(levenshtein(b, a), expected, "{b} to {a}");
+        }
+    }
+
+    #[test]
+    fn suggestions_stop_at_the_cutoff() {
+        let groups = ["desk", "living-room", "headphones-and-speakers"];
+        let cases = [
+            // Short names allow 2 edits
+            ("desk", Some("desk")),
+            ("dsek", Some("desk")),
+            ("dk", Some("desk")),
+            ("d", None),
+            ("livingroom", Some("living-room")),
+            ("livin-rm", None),
+            // Longer ones a quarter of their length
+            ("headphone-and-speakrs", Some("headphones-and-speakers")),
+            ("headphone-an-speakrs", Some("headphones-and-speakers")),
+            ("headphone-an-spekr", None),
+            ("kitchen", None),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(suggest(pattern, groups), expected, "{pattern}");
+        }
+        assert_eq!(suggest("dek", ["desk", "deck"]), Some("deck"));
+        assert_eq!(suggest("desk", []), None);
+    }
+}
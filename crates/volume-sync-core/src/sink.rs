@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::volume::VOLUME_NORM;
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize,
+)]
+pub enum DeviceKind {
+    #[default]
+    Sink,
+    Source,
+    /// An application's recording stream, a source output in PulseAudio terms
+    Recording,
+}
+
+/// Sinks, sources and recordings are numbered independently, so a device is only identified by
+/// both
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub struct DeviceId {
+    pub kind: DeviceKind,
+    pub index: u32,
+}
+
+impl DeviceId {
+    pub fn sink(index: u32) -> DeviceId {
+        DeviceId {
+            kind: DeviceKind::Sink,
+            index,
+        }
+    }
+
+    pub fn source(index: u32) -> DeviceId {
+        DeviceId {
+            kind: DeviceKind::Source,
+            index,
+        }
+    }
+
+    pub fn recording(index: u32) -> DeviceId {
+        DeviceId {
+            kind: DeviceKind::Recording,
+            index,
+        }
+    }
+}
+
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            DeviceKind::Sink => write!(f, "sink {}", self.index),
+            DeviceKind::Source => write!(f, "source {}", self.index),
+            DeviceKind::Recording => write!(f, "recording {}", self.index),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SinkDetails {
+    pub index: u32,
+    pub name: String,
+    /// Name shown to people, e.g. `HD650 DAC`
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Name of the sound card the device belongs to, e.g. `HDA Intel PCH`
+    #[serde(default)]
+    pub card_name: Option<String>,
+    #[serde(default)]
+    pub kind: DeviceKind,
+    pub properties: BTreeMap<String, String>,
+    /// Loudest channel volume
+    pub volume: u32,
+    #[serde(default)]
+    pub mute: bool,
+    pub active_port: Option<String>,
+    #[serde(default)]
+    pub ports: Vec<String>,
+    /// Index of the card the device belongs to
+    #[serde(default)]
+    pub card: Option<u32>,
+    #[serde(default)]
+    pub suspended: bool,
+    /// Configured sample spec, e.g. `s16le 2ch 48000Hz`
+    #[serde(default)]
+    pub sample_spec: String,
+    /// Configured sample rate in Hz
+    #[serde(default)]
+    pub rate: u32,
+    /// Supported stream formats
+    #[serde(default)]
+    pub formats: Vec<String>,
+    /// Seat the device's sound card is assigned to, unknown for devices without one
+    #[serde(default)]
+    pub seat: Option<String>,
+    /// Index of the source monitoring a sink
+    #[serde(default)]
+    pub monitor: Option<u32>,
+    /// Whether the sink's volume follows its loudest stream, as with flat volumes
+    #[serde(default)]
+    pub flat_volume: bool,
+    /// Whether the volume is set in hardware rather than in software
+    #[serde(default)]
+    pub hw_volume: bool,
+    /// Number of volume steps between silence and full volume, 0 if unknown
+    #[serde(default)]
+    pub volume_steps: u32,
+}
+
+impl SinkDetails {
+    /// Distance between the volumes a hardware volume device can take, if coarser than the volume
+    /// scale
+    pub fn volume_step(&self) -> Option<u32> {
+        if !self.hw_volume || self.volume_steps < 2 {
+            return None;
+        }
+        Some(VOLUME_NORM / (self.volume_steps - 1)).filter(|step| *step > 1)
+    }
+
+    /// Rounds `volume` to the nearest volume this device can take
+    pub fn round_to_step(&self, volume: u32) -> u32 {
+        match self.volume_step() {
+            Some(step) => (volume + step / 2) / step * step,
+            None => volume,
+        }
+    }
+
+    /// Identifies the device across reconnects and reboots, when its index and for some USB
+    /// devices even its name change
+    pub fn stable_id(&self) -> String {
+        let kind = match self.kind {
+            DeviceKind::Sink => "sink",
+            DeviceKind::Source => "source",
+            DeviceKind::Recording => "recording",
+        };
+        match self.properties.get("device.serial") {
+            Some(serial) => format!("{kind}:serial:{serial}"),
+            None => format!("{kind}:{}", self.name),
+        }
+    }
+
+    /// The description, taken from the properties for devices recorded before it had a field
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref().or_else(|| {
+            self.properties
+                .get("device.description")
+                .map(String::as_str)
+        })
+    }
+
+    /// The description, or the name for devices without one
+    pub fn display_name(&self) -> &str {
+        self.description().unwrap_or(&self.name)
+    }
+
+    /// Names the device for logs, e.g. `HD650 DAC (alsa_output.usb-...)`, falling back to the card
+    /// name when there is no description
+    pub fn label(&self) -> String {
+        match self.description().or(self.card_name.as_deref()) {
+            Some(description) => format!("{description} ({})", self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    pub fn id(&self) -> DeviceId {
+        DeviceId {
+            kind: self.kind,
+            index: self.index,
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::{env, fs, io};
+
+use serde::{Deserialize, Serialize};
+
+/// Everything remembered across restarts
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct State {
+    /// Last group level applied to a device, by its stable id
+    #[serde(default)]
+    pub devices: BTreeMap<String, u32>,
+    /// Last level propagated to every member of a group, by group name
+    #[serde(default)]
+    pub groups: BTreeMap<String, u32>,
+    /// Last group level for each combination of members' active ports, by group name
+    #[serde(default)]
+    pub ports: BTreeMap<String, BTreeMap<String, u32>>,
+    /// Offset of every member from the group level by stable id, by group name, for relative
+    /// groups
+    #[serde(default)]
+    pub offsets: BTreeMap<String, BTreeMap<String, i64>>,
+    /// Saved volumes of managed devices by stable id, by profile name
+    #[serde(default)]
+    pub profiles: BTreeMap<String, BTreeMap<String, u32>>,
+}
+
+pub fn get_file() -> String {
+    let dir = match env::var("XDG_STATE_HOME") {
+        Ok(v) => v,
+        Err(_) => match env::var("HOME") {
+            Ok(home) => format!("{home}/.local/state"),
+            Err(_) => {
+                tracing::error!("failed to load $HOME var");
+                ".".to_string()
+            }
+        },
+    };
+    format!("{dir}/volume-sync/state.toml")
+}
+
+pub fn load_state() -> State {
+    let filename = get_file();
+    match fs::read_to_string(&filename) {
+        Ok(content) => toml::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("ignoring invalid state file {filename}: {e}");
+            State::default()
+        }),
+        Err(_) => State::default(),
+    }
+}
+
+pub fn save_state(state: &State) -> io::Result<()> {
+    let filename = get_file();
+    if let Some(dir) = Path::new(&filename).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let content = toml::to_string(state).map_err(io::Error::other)?;
+    let tmp = format!("{filename}.tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(tmp, filename)
+}
@@ -0,0 +1,101 @@
+use std::fs;
+
+/// Values available to MQTT topic templates such as `home/{hostname}/audio/{group}/volume`
+#[derive(Clone, Debug, Default)]
+pub struct TemplateContext {
+    pub hostname: String,
+    pub instance: String,
+    pub group: Option<String>,
+}
+
+pub fn hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|h| h.trim().to_string())
+        .unwrap_or_else(|_| {
+            tracing::warn!("failed to read hostname");
+            "localhost".to_string()
+        })
+}
+
+impl TemplateContext {
+    pub fn new(instance: Option<&str>) -> TemplateContext {
+        let hostname = hostname();
+        TemplateContext {
+            instance: instance.map_or_else(|| hostname.clone(), |i| i.to_string()),
+            hostname,
+            group: None,
+        }
+    }
+
+    pub fn with_group(&self, group: &str) -> TemplateContext {
+        TemplateContext {
+            group: Some(group.to_string()),
+            ..self.clone()
+        }
+    }
+
+    fn get(&self, name: &str) -> Result<&str, String> {
+        let value = match name {
+            "hostname" => Some(&self.hostname),
+            "instance" => Some(&self.instance),
+            "group" => self.group.as_ref(),
+            _ => return Err(format!("unknown template variable: {{{name}}}")),
+        };
+        value
+            .map(|v| v.as_str())
+            .ok_or_else(|| format!("template variable {{{name}}} is not available here"))
+    }
+
+    /// Replaces every `{name}` in `template`, failing on unknown or unavailable variables
+    pub fn render(&self, template: &str) -> Result<String, String> {
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed '{{' in template: {template}"))?;
+            out.push_str(self.get(&rest[start + 1..start + end])?);
+            rest = &rest[start + end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_variables_and_rejects_the_rest() {
+        let context = TemplateContext {
+            hostname: "studio".to_string(),
+            instance: "desk".to_string(),
+            group: None,
+        };
+        let group = context.with_group("speakers");
+        let cases = [
+            ("volume-sync/status", Ok("volume-sync/status")),
+            (
+                "home/{hostname}/audio/{group}/volume",
+                Ok("home/studio/audio/speakers/volume"),
+            ),
+            ("{instance}{group}", Ok("deskspeakers")),
+            ("closing } alone", Ok("closing } alone")),
+            ("{volume}", Err("unknown template variable: {volume}")),
+            ("{}", Err("unknown template variable: {}")),
+            ("{Group}", Err("unknown template variable: {Group}")),
+            ("home/{group", Err("unclosed '{' in template: home/{group")),
+            ("{{group}}", Err("unknown template variable: {{group}")),
+        ];
+        for (template, expected) in cases {
+            let expected = expected.map(str::to_string).map_err(str::to_string);
+            assert_eq!(group.render(template), expected, "{template}");
+        }
+        assert_eq!(
+            context.render("{hostname}/{group}"),
+            Err("template variable {group} is not available here".to_string())
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sink::{DeviceId, DeviceKind, SinkDetails};
+
+/// A sound card and the profiles it can switch between
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CardDetails {
+    pub index: u32,
+    pub name: String,
+    pub active_profile: Option<String>,
+    pub profiles: Vec<String>,
+}
+
+/// An application stream playing to a sink
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct StreamDetails {
+    pub index: u32,
+    pub name: String,
+    /// Executable of the application, from `application.process.binary`
+    #[serde(default)]
+    pub binary: Option<String>,
+    /// Index of the sink the stream plays to
+    pub sink: u32,
+    pub volume: u32,
+    /// Whether the stream is paused by its application
+    #[serde(default)]
+    pub corked: bool,
+}
+
+/// Everything known about the server, kept up to date from events and checked against full
+/// listings from time to time so every feature answers from the same view
+#[derive(Debug, Default)]
+pub struct Topology {
+    pub cards: BTreeMap<u32, CardDetails>,
+    pub devices: BTreeMap<DeviceId, SinkDetails>,
+    pub streams: BTreeMap<u32, StreamDetails>,
+}
+
+/// Replaces `map` with `items`, logging how far the model had drifted from the server
+fn replace<K, V>(what: &str, map: &mut BTreeMap<K, V>, items: BTreeMap<K, V>)
+where
+    K: Ord + Debug,
+    V: PartialEq,
+{
+    let added = items.keys().filter(|k| !map.contains_key(k)).count();
+    let removed = map.keys().filter(|k| !items.contains_key(k)).count();
+    let changed = items
+        .iter()
+        .filter(|(k, v)| map.get(k).is_some_and(|old| old != *v))
+        .count();
+    if added + removed + changed > 0 && !map.is_empty() {
+        tracing::debug!("{what} drifted: {added} added, {removed} removed, {changed} changed");
+    }
+    *map = items;
+}
+
+impl Topology {
+    /// Updates a card, returning whether its active profile changed
+    pub fn update_card(&mut self, card: CardDetails) -> bool {
+        let previous = self.cards.insert(card.index, card.clone());
+        previous.is_some_and(|p| p.active_profile != card.active_profile)
+    }
+
+    pub fn remove_card(&mut self, index: u32) {
+        self.cards.remove(&index);
+    }
+
+    pub fn set_cards(&mut self, cards: Vec<CardDetails>) {
+        let cards = cards.into_iter().map(|c| (c.index, c)).collect();
+        replace("cards", &mut self.cards, cards);
+    }
+
+    /// Replaces every device of `kind`
+    pub fn set_devices(&mut self, kind: DeviceKind, devices: Vec<SinkDetails>) {
+        let (mut current, mut others): (BTreeMap<_, _>, BTreeMap<_, _>) =
+            std::mem::take(&mut self.devices)
+                .into_iter()
+                .partition(|(id, _)| id.kind == kind);
+        let devices = devices.into_iter().map(|d| (d.id(), d)).collect();
+        replace("devices", &mut current, devices);
+        others.extend(current);
+        self.devices = others;
+    }
+
+    pub fn update_stream(&mut self, stream: StreamDetails) {
+        self.streams.insert(stream.index, stream);
+    }
+
+    pub fn remove_stream(&mut self, index: u32) {
+        self.streams.remove(&index);
+    }
+
+    pub fn set_streams(&mut self, streams: Vec<StreamDetails>) {
+        let streams = streams.into_iter().map(|s| (s.index, s)).collect();
+        replace("streams", &mut self.streams, streams);
+    }
+
+    pub fn summary(&self) -> String {
+        let sinks = self
+            .devices
+            .keys()
+            .filter(|id| id.kind == DeviceKind::Sink)
+            .count();
+        format!(
+            "{} cards, {sinks} sinks, {} sources, {} streams",
+            self.cards.len(),
+            self.devices.len() - sinks,
+            self.streams.len()
+        )
+    }
+}
@@ -0,0 +1,317 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::volume::{percent_to_volume, VOLUME_MAX, VOLUME_NORM};
+
+/// One step turning the group level into a member's volume
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Offset,
+    Scale,
+    Curve,
+    Quantize,
+    Clamp,
+    SpreadLimit,
+}
+
+pub const DEFAULT_STAGES: [Stage; 6] = [
+    Stage::Offset,
+    Stage::Scale,
+    Stage::Curve,
+    Stage::Quantize,
+    Stage::Clamp,
+    Stage::SpreadLimit,
+];
+
+pub fn default_stages() -> Vec<Stage> {
+    DEFAULT_STAGES.to_vec()
+}
+
+/// How a member's volume differs from the group level; percentages are of 100% volume
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Translation {
+    /// Percent added to the level
+    #[serde(default)]
+    pub offset: i32,
+    /// Factor the level is multiplied by
+    #[serde(default = "default_factor")]
+    pub scale: f64,
+    /// Exponent applied to the level relative to 100%
+    #[serde(default = "default_factor")]
+    pub curve: f64,
+    /// Step in percent the volume is rounded to, 0 to disable
+    #[serde(default)]
+    pub quantize: u32,
+    #[serde(default)]
+    pub min: u32,
+    pub max: Option<u32>,
+    /// Largest difference in percent from the group level
+    pub spread_limit: Option<u32>,
+}
+
+impl Translation {
+    /// Short description of the settings that differ from the defaults, e.g. `offset +10%, max 80%`
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if self.offset != 0 {
+            parts.push(format!("offset {:+}%", self.offset));
+        }
+        if self.scale != 1.0 {
+            parts.push(format!("scale {}", self.scale));
+        }
+        if self.curve != 1.0 {
+            parts.push(format!("curve {}", self.curve));
+        }
+        if self.quantize != 0 {
+            parts.push(format!("quantize {}%", self.quantize));
+        }
+        if self.min != 0 {
+            parts.push(format!("min {}%", self.min));
+        }
+        if let Some(max) = self.max {
+            parts.push(format!("max {max}%"));
+        }
+        if let Some(spread_limit) = self.spread_limit {
+            parts.push(format!("spread limit {spread_limit}%"));
+        }
+        parts.join(", ")
+    }
+}
+
+fn default_factor() -> f64 {
+    1.0
+}
+
+impl Default for Translation {
+    fn default() -> Translation {
+        Translation {
+            offset: 0,
+            scale: default_factor(),
+            curve: default_factor(),
+            quantize: 0,
+            min: 0,
+            max: None,
+            spread_limit: None,
+        }
+    }
+}
+
+fn to_volume(volume: f64) -> u32 {
+    volume.round().clamp(0.0, VOLUME_MAX as f64) as u32
+}
+
+fn offset(volume: u32, percent: i32) -> u32 {
+    let delta = percent_to_volume(percent.unsigned_abs());
+    if percent < 0 {
+        volume.saturating_sub(delta)
+    } else {
+        volume.saturating_add(delta).min(VOLUME_MAX)
+    }
+}
+
+impl Translation {
+    /// Runs a single stage; `level` is the group level that entered the pipeline
+    pub fn stage(&self, stage: Stage, level: u32, volume: u32) -> u32 {
+        match stage {
+            Stage::Offset => offset(volume, self.offset),
+            Stage::Scale => to_volume(volume as f64 * self.scale),
+            Stage::Curve => {
+                let relative = volume as f64 / VOLUME_NORM as f64;
+                to_volume(relative.powf(self.curve) * VOLUME_NORM as f64)
+            }
+            Stage::Quantize if self.quantize > 0 => {
+                let step = percent_to_volume(self.quantize).max(1);
+                to_volume((volume as f64 / step as f64).round() * step as f64)
+            }
+            Stage::Quantize => volume,
+            Stage::Clamp => {
+                let max = self.max.map_or(VOLUME_MAX, percent_to_volume);
+                volume.max(percent_to_volume(self.min)).min(max)
+            }
+            Stage::SpreadLimit => match self.spread_limit {
+                Some(limit) => {
+                    let limit = percent_to_volume(limit);
+                    volume
+                        .max(level.saturating_sub(limit))
+                        .min(level.saturating_add(limit))
+                }
+                None => volume,
+            },
+        }
+    }
+
+    /// The volume after each stage in `stages`, starting from `level`
+    pub fn trace(&self, stages: &[Stage], level: u32) -> Vec<(Stage, u32)> {
+        let mut volume = level;
+        stages
+            .iter()
+            .map(|stage| {
+                volume = self.stage(*stage, level, volume);
+                (*stage, volume)
+            })
+            .collect()
+    }
+
+    /// The member volume for a group `level`
+    pub fn apply(&self, stages: &[Stage], level: u32) -> u32 {
+        self.trace(stages, level).last().map_or(level, |(_, v)| *v)
+    }
+
+    /// The group level a member `volume` corresponds to, undoing the stages that can be undone;
+    /// quantize, clamp and spread limit are kept as they are
+    pub fn invert(&self, stages: &[Stage], volume: u32) -> u32 {
+        stages
+            .iter()
+            .rev()
+            .fold(volume, |volume, stage| match stage {
+                Stage::Offset => offset(volume, -self.offset),
+                Stage::Scale if self.scale > 0.0 => to_volume(volume as f64 / self.scale),
+                Stage::Curve if self.curve > 0.0 => {
+                    let relative = volume as f64 / VOLUME_NORM as f64;
+                    to_volume(relative.powf(1.0 / self.curve) * VOLUME_NORM as f64)
+                }
+                _ => volume,
+            })
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Stage::Offset => "offset",
+            Stage::Scale => "scale",
+            Stage::Curve => "curve",
+            Stage::Quantize => "quantize",
+            Stage::Clamp => "clamp",
+            Stage::SpreadLimit => "spread_limit",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::volume::percent_to_volume as p;
+
+    #[test]
+    fn stages_apply_one_setting_each() {
+        let t = Translation {
+            offset: -10,
+            scale: 0.5,
+            curve: 2.0,
+            quantize: 10,
+            min: 20,
+            max: Some(80),
+            spread_limit: Some(15),
+        };
+        let level = p(50);
+        let cases = [
+            (Stage::Offset, p(50), p(50) - p(10)),
+            (Stage::Offset, p(5), 0),
+            (Stage::Scale, p(50), p(25)),
+            (Stage::Curve, p(50), p(25)),
+            (Stage::Curve, p(100), p(100)),
+            (Stage::Quantize, p(47), 5 * p(10)),
+            (Stage::Quantize, p(44), 4 * p(10)),
+            (Stage::Clamp, p(10), p(20)),
+            (Stage::Clamp, p(50), p(50)),
+            (Stage::Clamp, p(90), p(80)),
+            (Stage::SpreadLimit, p(80), level + p(15)),
+            (Stage::SpreadLimit, p(20), level - p(15)),
+            (Stage::SpreadLimit, p(55), p(55)),
+        ];
+        for (stage, volume, expected) in cases {
+            assert_eq!(
+                t.stage(stage, level, volume),
+                expected,
+                "{stage} of {volume}"
+            );
+        }
+        let unset = Translation::default();
+        for stage in DEFAULT_STAGES {
+            assert_eq!(unset.stage(stage, level, p(30)), p(30), "{stage}");
+        }
+    }
+
+    #[test]
+    fn stages_run_in_the_given_order() {
+        let t = Translation {
+            offset: 10,
+            scale: 2.0,
+            ..Translation::default()
+        };
+        let level = p(20);
+        assert_eq!(
+            t.apply(&[Stage::Offset, Stage::Scale], level),
+            (p(20) + p(10)) * 2
+        );
+        assert_eq!(
+            t.apply(&[Stage::Scale, Stage::Offset], level),
+            p(20) * 2 + p(10)
+        );
+        assert_eq!(t.apply(&[], level), level);
+        assert_eq!(
+            t.trace(&DEFAULT_STAGES, level)
+                .iter()
+                .map(|(stage, _)| *stage)
+                .collect::<Vec<_>>(),
+            DEFAULT_STAGES
+        );
+    }
+
+    #[test]
+    fn inversion_round_trips() {
+        let translations = [
+            Translation {
+                offset: -10,
+                ..Translation::default()
+            },
+            Translation {
+                offset: 15,
+                ..Translation::default()
+            },
+            Translation {
+                scale: 0.8,
+                ..Translation::default()
+            },
+            Translation {
+                curve: 1.5,
+                ..Translation::default()
+            },
+            Translation {
+                offset: -5,
+                scale: 1.2,
+                curve: 0.7,
+                ..Translation::default()
+            },
+        ];
+        for t in &translations {
+            for percent in (20..=100).step_by(10) {
+                let level = p(percent);
+                let back = t.invert(&DEFAULT_STAGES, t.apply(&DEFAULT_STAGES, level));
+                assert!(
+                    back.abs_diff(level) <= 2,
+                    "{} at {percent}%: {back} instead of {level}",
+                    t.summary()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lossy_stages_are_not_inverted() {
+        let t = Translation {
+            quantize: 10,
+            max: Some(50),
+            spread_limit: Some(5),
+            ..Translation::default()
+        };
+        for volume in [p(33), p(50), p(80)] {
+            assert_eq!(t.invert(&DEFAULT_STAGES, volume), volume);
+        }
+    }
+}
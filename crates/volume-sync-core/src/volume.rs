@@ -0,0 +1,309 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Volume of a sink at 100%, the same scale as `PA_VOLUME_NORM`
+pub const VOLUME_NORM: u32 = 0x10000;
+/// Largest volume mixers usually offer, +11dB, the same as `PA_VOLUME_UI_MAX`
+pub const VOLUME_UI_MAX: u32 = 99957;
+/// Largest valid volume, the same as `PA_VOLUME_MAX`
+pub const VOLUME_MAX: u32 = u32::MAX / 2;
+
+/// A volume change, in percent or in hundredths of a dB
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum VolumeSpec {
+    Absolute(u32),
+    Increase(u32),
+    Decrease(u32),
+    /// Set to a level in hundredths of a dB, e.g. `=-6dB`
+    AbsoluteDb(i32),
+    /// Raise or lower by hundredths of a dB, e.g. `+3dB` or `-3dB`
+    Gain(i32),
+}
+
+/// Largest percentage accepted, the percentage of `VOLUME_MAX`
+pub const MAX_PERCENT: u32 = (VOLUME_MAX as u64 * 100 / VOLUME_NORM as u64) as u32;
+/// Largest level or gain accepted in dB either way, about the dB of `VOLUME_MAX`
+pub const MAX_DB: u32 = 270;
+
+pub fn percent_to_volume(percent: u32) -> u32 {
+    let raw = VOLUME_NORM as u64 * percent as u64 / 100;
+    raw.min(VOLUME_MAX as u64) as u32
+}
+
+/// Whole percentage of full volume, rounded
+pub fn volume_to_percent(volume: u32) -> u32 {
+    ((volume as u64 * 100 + VOLUME_NORM as u64 / 2) / VOLUME_NORM as u64) as u32
+}
+
+/// Raw volume at a level in dB, using the server's cubic mapping
+pub fn db_to_volume(db: f64) -> u32 {
+    let raw = VOLUME_NORM as f64 * 10f64.powf(db / 60.0);
+    raw.round().clamp(0.0, VOLUME_MAX as f64) as u32
+}
+
+/// Level in dB of a raw volume, negative infinity when muted
+pub fn volume_to_db(volume: u32) -> f64 {
+    60.0 * (volume as f64 / VOLUME_NORM as f64).log10()
+}
+
+/// Parses a whole percentage like `40` or `40%`, rejecting signs, spaces and values past
+/// `MAX_PERCENT`
+pub fn parse_percent(s: &str) -> Result<u32, String> {
+    let digits = s.strip_suffix('%').unwrap_or(s);
+    if digits.is_empty() || digits.len() > 10 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("invalid volume: {s}"));
+    }
+    digits
+        .parse::<u32>()
+        .ok()
+        .filter(|p| *p <= MAX_PERCENT)
+        .ok_or_else(|| format!("volume out of range: {s}"))
+}
+
+/// Parses an unsigned decimal number of dB with at most two decimals, like `3` or `1.25`, into
+/// hundredths of a dB
+fn parse_db(s: &str) -> Result<i32, String> {
+    let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+    let valid = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty()
+        || whole.len() > 3
+        || fraction.len() > 2
+        || !valid(whole)
+        || !valid(fraction)
+    {
+        return Err(format!("invalid volume: {s}dB"));
+    }
+    let whole: u32 = whole
+        .parse()
+        .map_err(|_| format!("invalid volume: {s}dB"))?;
+    let fraction: u32 = format!("{fraction:0<2}").parse().unwrap_or(0);
+    whole
+        .checked_mul(100)
+        .and_then(|h| h.checked_add(fraction))
+        .filter(|h| *h <= MAX_DB * 100)
+        .map(|h| h as i32)
+        .ok_or_else(|| format!("volume out of range: {s}dB"))
+}
+
+fn format_db(hundredths: i32) -> String {
+    let db = format!(
+        "{}.{:02}",
+        hundredths.unsigned_abs() / 100,
+        hundredths.unsigned_abs() % 100
+    );
+    db.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// A difference in volume given in percent, e.g. `1%` or `0.5%`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Delta(u32);
+
+impl Delta {
+    /// The difference in raw volume units
+    pub fn volume(&self) -> u32 {
+        self.0
+    }
+}
+
+impl FromStr for Delta {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let percent = s
+            .trim_end_matches('%')
+            .parse::<f64>()
+            .ok()
+            .filter(|p| p.is_finite() && *p >= 0.0)
+            .ok_or_else(|| format!("invalid volume difference: {s}"))?;
+        let raw = (VOLUME_NORM as f64 * percent / 100.0).round();
+        Ok(Delta(raw.min(VOLUME_MAX as f64) as u32))
+    }
+}
+
+impl TryFrom<String> for Delta {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Delta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = self.0 as f64 * 100.0 / VOLUME_NORM as f64;
+        write!(f, "{}%", (percent * 100.0).round() / 100.0)
+    }
+}
+
+impl From<Delta> for String {
+    fn from(delta: Delta) -> String {
+        delta.to_string()
+    }
+}
+
+impl VolumeSpec {
+    /// The new level of a sink currently at `current`
+    pub fn apply(&self, current: u32) -> u32 {
+        match self {
+            VolumeSpec::Absolute(percent) => percent_to_volume(*percent),
+            VolumeSpec::Increase(percent) => {
+                let limit = VOLUME_NORM.max(current);
+                current
+                    .saturating_add(percent_to_volume(*percent))
+                    .min(limit)
+            }
+            VolumeSpec::Decrease(percent) => current.saturating_sub(percent_to_volume(*percent)),
+            VolumeSpec::AbsoluteDb(hundredths) => db_to_volume(*hundredths as f64 / 100.0),
+            VolumeSpec::Gain(hundredths) => {
+                let volume = db_to_volume(volume_to_db(current) + *hundredths as f64 / 100.0);
+                if *hundredths > 0 {
+                    volume.min(VOLUME_NORM.max(current))
+                } else {
+                    volume
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for VolumeSpec {
+    type Err = String;
+
+    /// Parses `40`, `=40%`, `+5%`, `-5`, `=-6dB`, `+3dB` or `-1.5dB`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(db) = s.strip_suffix("dB") {
+            return if let Some(level) = db.strip_prefix("=-") {
+                Ok(VolumeSpec::AbsoluteDb(-parse_db(level)?))
+            } else if let Some(level) = db.strip_prefix('=') {
+                Ok(VolumeSpec::AbsoluteDb(parse_db(level)?))
+            } else if let Some(gain) = db.strip_prefix('+') {
+                Ok(VolumeSpec::Gain(parse_db(gain)?))
+            } else if let Some(gain) = db.strip_prefix('-') {
+                Ok(VolumeSpec::Gain(-parse_db(gain)?))
+            } else {
+                Err(format!("ambiguous volume, use =, + or -: {s}"))
+            };
+        }
+        if let Some(v) = s.strip_prefix('+') {
+            Ok(VolumeSpec::Increase(parse_percent(v)?))
+        } else if let Some(v) = s.strip_prefix('-') {
+            Ok(VolumeSpec::Decrease(parse_percent(v)?))
+        } else {
+            Ok(VolumeSpec::Absolute(parse_percent(
+                s.strip_prefix('=').unwrap_or(s),
+            )?))
+        }
+    }
+}
+
+impl fmt::Display for VolumeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VolumeSpec::Absolute(percent) => write!(f, "{percent}%"),
+            VolumeSpec::Increase(percent) => write!(f, "+{percent}%"),
+            VolumeSpec::Decrease(percent) => write!(f, "-{percent}%"),
+            VolumeSpec::AbsoluteDb(hundredths) if *hundredths < 0 => {
+                write!(f, "=-{}dB", format_db(*hundredths))
+            }
+            VolumeSpec::AbsoluteDb(hundredths) => write!(f, "={}dB", format_db(*hundredths)),
+            VolumeSpec::Gain(hundredths) if *hundredths < 0 => {
+                write!(f, "-{}dB", format_db(*hundredths))
+            }
+            VolumeSpec::Gain(hundredths) => write!(f, "+{}dB", format_db(*hundredths)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_boundaries() {
+        let max = MAX_PERCENT.to_string();
+        let past_max = (MAX_PERCENT + 1).to_string();
+        let cases = [
+            ("0", Ok(0)),
+            ("40", Ok(40)),
+            ("40%", Ok(40)),
+            (max.as_str(), Ok(MAX_PERCENT)),
+            (past_max.as_str(), Err("volume out of range")),
+            ("4294967295", Err("volume out of range")),
+            ("99999999999", Err("invalid volume")),
+            ("", Err("invalid volume")),
+            ("%", Err("invalid volume")),
+            ("+40", Err("invalid volume")),
+            ("-1", Err("invalid volume")),
+            (" 40", Err("invalid volume")),
+            ("40%%", Err("invalid volume")),
+            ("4.5", Err("invalid volume")),
+        ];
+        for (input, expected) in cases {
+            let result = parse_percent(input);
+            match expected {
+                Ok(percent) => assert_eq!(result, Ok(percent), "{input:?}"),
+                Err(prefix) => assert!(
+                    result.as_ref().is_err_and(|e| e.starts_with(prefix)),
+                    "{input:?}: {result:?}"
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn db_boundaries() {
+        let cases = [
+            ("0", Ok(0)),
+            ("3", Ok(300)),
+            ("1.5", Ok(150)),
+            ("1.25", Ok(125)),
+            ("0.01", Ok(1)),
+            ("270", Ok(27000)),
+            ("270.00", Ok(27000)),
+            ("270.01", Err("volume out of range")),
+            ("999", Err("volume out of range")),
+            ("1000", Err("invalid volume")),
+            ("1.255", Err("invalid volume")),
+            ("", Err("invalid volume")),
+            (".5", Err("invalid volume")),
+            ("+3", Err("invalid volume")),
+            ("-3", Err("invalid volume")),
+            ("1.-5", Err("invalid volume")),
+            ("1e2", Err("invalid volume")),
+        ];
+        for (input, expected) in cases {
+            let result = parse_db(input);
+            match expected {
+                Ok(hundredths) => assert_eq!(result, Ok(hundredths), "{input:?}"),
+                Err(prefix) => assert!(
+                    result.as_ref().is_err_and(|e| e.starts_with(prefix)),
+                    "{input:?}: {result:?}"
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn volume_specs() {
+        let cases = [
+            ("40", Some(VolumeSpec::Absolute(40))),
+            ("=40%", Some(VolumeSpec::Absolute(40))),
+            ("+5%", Some(VolumeSpec::Increase(5))),
+            ("-5", Some(VolumeSpec::Decrease(5))),
+            ("=-6dB", Some(VolumeSpec::AbsoluteDb(-600))),
+            ("=6dB", Some(VolumeSpec::AbsoluteDb(600))),
+            ("+3dB", Some(VolumeSpec::Gain(300))),
+            ("-1.5dB", Some(VolumeSpec::Gain(-150))),
+            ("3dB", None),
+            ("+-5", None),
+            ("=--6dB", None),
+            ("+270.01dB", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(input.parse::<VolumeSpec>().ok(), expected, "{input:?}");
+        }
+    }
+}
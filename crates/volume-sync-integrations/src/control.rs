@@ -0,0 +1,156 @@
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::Duration;
+
+use volume_sync_core::command::{Command, Reply, MAX_COMMAND_LEN};
+use volume_sync_core::event::VolumeSyncEvent;
+
+use crate::monitor::Monitors;
+
+pub fn get_socket() -> String {
+    match env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) => format!("{dir}/volume-sync.sock"),
+        Err(_) => {
+            tracing::warn!("failed to load $XDG_RUNTIME_DIR var");
+            "/tmp/volume-sync.sock".to_string()
+        }
+    }
+}
+
+/// Time a client gets to send its command before it is dropped
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reads one command line of at most `MAX_COMMAND_LEN` bytes, which must be valid UTF-8
+fn read_command(stream: &UnixStream) -> std::io::Result<Result<String, String>> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut line = Vec::new();
+    BufReader::new(stream.take(MAX_COMMAND_LEN as u64 + 1)).read_until(b'\n', &mut line)?;
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    } else if line.len() > MAX_COMMAND_LEN {
+        return Ok(Err(format!("command longer than {MAX_COMMAND_LEN} bytes")));
+    }
+    Ok(String::from_utf8(line).map_err(|_| "command is not valid UTF-8".to_string()))
+}
+
+fn handle_client(
+    stream: UnixStream,
+    sender: &Sender<VolumeSyncEvent>,
+    monitors: &Monitors,
+) -> std::io::Result<()> {
+    let line = read_command(&stream)?;
+    tracing::debug!("control command: {line:?}");
+
+    // Monitors keep their connection, so they never reach the engine
+    if let Ok(line) = &line {
+        match line.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            ["monitor"] => return monitors.add(stream, None),
+            ["monitor", group] => return monitors.add(stream, Some(group.to_string())),
+            _ => {}
+        }
+    }
+
+    let reply = match line.and_then(|line| Command::parse(&line)) {
+        Ok(command) => {
+            let (reply_sender, reply_receiver) = channel();
+            sender
+                .send(VolumeSyncEvent::Command(command, reply_sender))
+                .expect("failed to send command event");
+            reply_receiver
+                .recv()
+                .unwrap_or_else(|_| Err("no reply from daemon".to_string()))
+        }
+        Err(e) => Err(e),
+    };
+
+    let mut stream = stream;
+    match reply {
+        Ok(msg) => writeln!(stream, "ok {msg}"),
+        Err(msg) => writeln!(stream, "error {msg}"),
+    }
+}
+
+pub fn start_listener(sender: Sender<VolumeSyncEvent>, monitors: Monitors) -> std::io::Result<()> {
+    let path = get_socket();
+    if UnixStream::connect(&path).is_ok() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AddrInUse,
+            format!("another instance is listening on {path}"),
+        ));
+    }
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    tracing::info!("listening for commands on {path}");
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = handle_client(stream, &sender, &monitors) {
+                        tracing::warn!("control client error: {e}");
+                    }
+                }
+                Err(e) => tracing::error!("control socket error: {e}"),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Stops accepting commands by removing the socket
+pub fn stop_listener() {
+    let path = get_socket();
+    if let Err(e) = fs::remove_file(&path) {
+        tracing::warn!("failed to remove control socket {path}: {e}");
+    }
+}
+
+pub fn send_command(command: &str) -> Reply {
+    let path = get_socket();
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|e| format!("failed to connect to daemon at {path}: {e}"))?;
+    writeln!(stream, "{command}").map_err(|e| e.to_string())?;
+
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .map_err(|e| e.to_string())?;
+    let reply = reply.trim_end();
+    match reply.split_once(' ').unwrap_or((reply, "")) {
+        ("ok", msg) => Ok(msg.to_string()),
+        ("error", msg) => Err(msg.to_string()),
+        _ => Err(format!("unexpected reply: {reply}")),
+    }
+}
+
+/// Sends `command`, a `monitor` command, and copies every line the daemon streams back to `out`
+/// until the daemon goes away
+pub fn follow(command: &str, mut out: impl Write) -> Result<(), String> {
+    let path = get_socket();
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|e| format!("failed to connect to daemon at {path}: {e}"))?;
+    writeln!(stream, "{command}").map_err(|e| e.to_string())?;
+
+    let mut lines = BufReader::new(stream).lines();
+    match lines.next() {
+        Some(Ok(reply)) if reply == "ok" => {}
+        Some(Ok(reply)) => {
+            return Err(reply
+                .strip_prefix("error ")
+                .map_or_else(|| format!("unexpected reply: {reply}"), str::to_string))
+        }
+        Some(Err(e)) => return Err(e.to_string()),
+        None => return Err("no reply from daemon".to_string()),
+    }
+    for line in lines {
+        let line = line.map_err(|e| e.to_string())?;
+        writeln!(out, "{line}")
+            .and_then(|()| out.flush())
+            .map_err(|e| e.to_string())?;
+    }
+    Err("the daemon closed the connection".to_string())
+}
@@ -0,0 +1,79 @@
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::message::Type;
+use zbus::MatchRule;
+
+use volume_sync_core::event::VolumeSyncEvent;
+
+#[zbus::proxy(
+    interface = "com.feralinteractive.GameMode",
+    default_service = "com.feralinteractive.GameMode",
+    default_path = "/com/feralinteractive/GameMode"
+)]
+trait GameMode {
+    #[zbus(property)]
+    fn client_count(&self) -> zbus::Result<i32>;
+}
+
+fn send(sender: &Sender<VolumeSyncEvent>, active: bool) {
+    sender
+        .send(VolumeSyncEvent::Performance(active))
+        .expect("failed to send performance event");
+}
+
+/// Sends `Performance` whenever gamemode starts or stops running games
+pub fn start_listener(sender: Sender<VolumeSyncEvent>) -> zbus::Result<()> {
+    let connection = Connection::session()?;
+    let gamemode = GameModeProxyBlocking::new(&connection)?;
+    let changes = gamemode.receive_client_count_changed();
+    if let Ok(count) = gamemode.client_count() {
+        send(&sender, count > 0);
+    }
+
+    thread::spawn(move || {
+        let _gamemode = &gamemode;
+        for change in changes {
+            match change.get() {
+                Ok(count) => {
+                    tracing::info!("gamemode running {count} games");
+                    send(&sender, count > 0);
+                }
+                Err(e) => tracing::warn!("invalid gamemode ClientCount: {e}"),
+            }
+        }
+        tracing::warn!("gamemode property stream ended");
+    });
+    Ok(())
+}
+
+/// Sends `Performance` with the boolean argument of every `signal`, given as
+/// `interface.Member` on the session bus
+pub fn start_signal_listener(sender: Sender<VolumeSyncEvent>, signal: &str) -> zbus::Result<()> {
+    let (interface, member) = signal
+        .rsplit_once('.')
+        .ok_or_else(|| zbus::Error::Failure(format!("invalid signal \"{signal}\"")))?;
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .interface(interface.to_string())?
+        .member(member.to_string())?
+        .build();
+    let connection = Connection::session()?;
+    let messages = MessageIterator::for_match_rule(rule, &connection, None)?;
+
+    let signal = signal.to_string();
+    thread::spawn(move || {
+        for message in messages {
+            match message.and_then(|m| m.body().deserialize::<bool>()) {
+                Ok(active) => {
+                    tracing::info!("{signal}: performance mode {active}");
+                    send(&sender, active);
+                }
+                Err(e) => tracing::warn!("invalid {signal} signal: {e}"),
+            }
+        }
+        tracing::warn!("{signal} signal stream ended");
+    });
+    Ok(())
+}
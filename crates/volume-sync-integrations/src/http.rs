@@ -0,0 +1,197 @@
+use std::io::Read;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use volume_sync_core::command::{self, Command, MAX_COMMAND_LEN};
+use volume_sync_core::event::VolumeSyncEvent;
+use volume_sync_core::sink::DeviceKind;
+use volume_sync_core::volume::VolumeSpec;
+
+/// Decodes `%XX` escapes in a path segment, e.g. `living%20room`
+fn percent_decode(segment: &str) -> Result<String, String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = segment
+                .get(i + 1..i + 3)
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| format!("invalid escape in {segment}"))?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| format!("{segment} is not valid UTF-8"))
+}
+
+/// Reads a volume from a body like `40%` or `{"volume": "+5%"}`, numbers being percentages
+fn parse_volume(body: &str) -> Result<VolumeSpec, String> {
+    let body = body.trim();
+    if !body.starts_with('{') {
+        return body.parse();
+    }
+    let value: Value = serde_json::from_str(body).map_err(|e| format!("invalid JSON: {e}"))?;
+    match &value["volume"] {
+        Value::String(volume) => volume.parse(),
+        Value::Number(volume) => volume.to_string().parse(),
+        _ => Err("missing \"volume\"".to_string()),
+    }
+}
+
+/// The command a request stands for, and whether its reply already is JSON
+fn route(method: &Method, url: &str, body: &str) -> Result<(Command, bool), (u16, String)> {
+    let path = url.split_once('?').map_or(url, |(path, _)| path);
+    let segments = path
+        .trim_matches('/')
+        .split('/')
+        .map(percent_decode)
+        .collect::<Result<Vec<String>, String>>()
+        .map_err(|e| (400, e))?;
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    match (method, segments.as_slice()) {
+        (Method::Get, ["groups"]) => Ok((Command::Snapshot(None), true)),
+        (Method::Get, ["sinks"]) => Ok((
+            Command::Resolve {
+                pattern: "*".to_string(),
+                kind: Some(DeviceKind::Sink),
+                json: true,
+            },
+            true,
+        )),
+        (Method::Post, ["groups", group, "volume"]) => {
+            command::validate(group).map_err(|e| (400, e))?;
+            let volume = parse_volume(body).map_err(|e| (400, e))?;
+            let group = group.to_string();
+            Ok((Command::SetVolume { group, volume }, false))
+        }
+        (Method::Post, ["resync"]) => Ok((Command::Resync, false)),
+        (_, ["groups" | "sinks" | "resync"] | ["groups", _, "volume"]) => {
+            Err((405, format!("{method} not allowed on {path}")))
+        }
+        _ => Err((404, format!("not found: {path}"))),
+    }
+}
+
+fn handle_request(mut request: Request, sender: &Sender<VolumeSyncEvent>) -> std::io::Result<()> {
+    let mut body = Vec::new();
+    let read = request
+        .as_reader()
+        .take(MAX_COMMAND_LEN as u64 + 1)
+        .read_to_end(&mut body);
+    tracing::debug!("HTTP request: {} {}", request.method(), request.url());
+
+    // Checked before decoding, as the limit may cut a character in half
+    let result = match read {
+        Err(e) => Err((400, format!("failed to read body: {e}"))),
+        Ok(_) if body.len() > MAX_COMMAND_LEN => {
+            Err((413, format!("body longer than {MAX_COMMAND_LEN} bytes")))
+        }
+        Ok(_) => match String::from_utf8(body) {
+            Ok(body) => route(request.method(), request.url(), &body),
+            Err(_) => Err((400, "body is not valid UTF-8".to_string())),
+        },
+    };
+    let (status, text) = match result {
+        Ok((command, json)) => {
+            let (reply_sender, reply_receiver) = channel();
+            sender
+                .send(VolumeSyncEvent::Command(command, reply_sender))
+                .expect("failed to send command event");
+            match reply_receiver.recv() {
+                Ok(Ok(reply)) if json => (200, reply),
+                Ok(Ok(reply)) => (200, json!({ "result": reply }).to_string()),
+                Ok(Err(e)) => (400, json!({ "error": e }).to_string()),
+                Err(_) => (500, json!({ "error": "no reply from daemon" }).to_string()),
+            }
+        }
+        Err((status, e)) => (status, json!({ "error": e }).to_string()),
+    };
+    let header = Header::from_bytes("Content-Type", "application/json")
+        .expect("invalid Content-Type header");
+    request.respond(
+        Response::from_string(text)
+            .with_status_code(status)
+            .with_header(header),
+    )
+}
+
+/// The running server, kept to stop it at shutdown
+static SERVER: Mutex<Option<Arc<Server>>> = Mutex::new(None);
+
+/// Serves the REST API on `address`, answering one request at a time through the command layer
+pub fn start_listener(address: &str, sender: Sender<VolumeSyncEvent>) -> Result<(), String> {
+    let server =
+        Server::http(address).map_err(|e| format!("failed to listen on {address}: {e}"))?;
+    tracing::info!("serving the HTTP API on {address}");
+    let server = Arc::new(server);
+    *SERVER.lock().expect("failed to lock HTTP server") = Some(server.clone());
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            if let Err(e) = handle_request(request, &sender) {
+                tracing::warn!("HTTP client error: {e}");
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Stops accepting requests, if the API is served
+pub fn stop_listener() {
+    if let Some(server) = SERVER.lock().expect("failed to lock HTTP server").take() {
+        tracing::info!("stopping HTTP API");
+        server.unblock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_only_hex_escapes() {
+        let cases = [
+            ("living%20room", Some("living room")),
+            ("desk", Some("desk")),
+            ("%C3%A9", Some("\u{e9}")),
+            ("%+1", None),
+            ("%-1", None),
+            ("%2", None),
+            ("%zz", None),
+            ("%FF", None),
+        ];
+        for (segment, expected) in cases {
+            assert_eq!(
+                percent_decode(segment).ok().as_deref(),
+                expected,
+                "{segment}"
+            );
+        }
+    }
+
+    #[test]
+    fn routes_sinks_and_validates_group_names() {
+        assert!(matches!(
+            route(&Method::Get, "/sinks", ""),
+            Ok((Command::Resolve { pattern, kind: Some(DeviceKind::Sink), json: true }, _)) if pattern == "*"
+        ));
+        let route = |url: &str| route(&Method::Post, url, "40").map(|(command, _)| command);
+        assert!(matches!(
+            route("/groups/living%20room/volume"),
+            Ok(Command::SetVolume { group, .. }) if group == "living room"
+        ));
+        let long = format!("/groups/{}/volume", "a".repeat(MAX_COMMAND_LEN + 1));
+        for url in ["/groups/desk%0A/volume", "/groups/%1Bdesk/volume", &long] {
+            assert!(matches!(route(url), Err((400, _))), "{url}");
+        }
+    }
+}
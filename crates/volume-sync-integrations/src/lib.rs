@@ -0,0 +1,14 @@
+pub mod control;
+#[cfg(feature = "dbus")]
+pub mod gamemode;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "dbus")]
+pub mod logind;
+pub mod monitor;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod notifications;
+pub mod signals;
+#[cfg(feature = "tray")]
+pub mod tray;
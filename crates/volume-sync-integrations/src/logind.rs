@@ -0,0 +1,41 @@
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use zbus::blocking::Connection;
+
+use volume_sync_core::event::VolumeSyncEvent;
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+/// Sends `Resumed` whenever logind reports the system woke up from sleep
+pub fn start_listener(sender: Sender<VolumeSyncEvent>) -> zbus::Result<()> {
+    let connection = Connection::system()?;
+    let manager = ManagerProxyBlocking::new(&connection)?;
+    let signals = manager.receive_prepare_for_sleep()?;
+
+    thread::spawn(move || {
+        let _manager = manager;
+        for signal in signals {
+            match signal.args() {
+                Ok(args) if args.start => tracing::info!("system going to sleep"),
+                Ok(_) => {
+                    tracing::info!("system resumed");
+                    sender
+                        .send(VolumeSyncEvent::Resumed)
+                        .expect("failed to send resume event");
+                }
+                Err(e) => tracing::warn!("invalid PrepareForSleep signal: {e}"),
+            }
+        }
+        tracing::warn!("logind signal stream ended");
+    });
+    Ok(())
+}
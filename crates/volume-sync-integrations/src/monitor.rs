@@ -0,0 +1,112 @@
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use volume_sync_core::group::Snapshot;
+use volume_sync_core::volume::volume_to_percent;
+
+/// Time a monitor client gets to take a line before it is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+struct Client {
+    stream: UnixStream,
+    /// Group to report, every group if unset
+    group: Option<String>,
+    last: String,
+}
+
+#[derive(Default)]
+struct Clients {
+    clients: Vec<Client>,
+    snapshots: Vec<Snapshot>,
+}
+
+/// Clients of `volume-sync monitor`, each sent a line of JSON whenever the state it follows
+/// changes
+#[derive(Clone, Default)]
+pub struct Monitors {
+    clients: Arc<Mutex<Clients>>,
+}
+
+/// Status of a group, with the fields Waybar reads from custom modules next to its own
+fn status(snapshot: &Snapshot) -> Value {
+    let volume = snapshot.level.map(volume_to_percent);
+    let text = match volume {
+        _ if snapshot.muted => "muted".to_string(),
+        Some(volume) => format!("{volume}%"),
+        None => "-".to_string(),
+    };
+    let members = snapshot.members.len();
+    json!({
+        "group": snapshot.group,
+        "volume": volume,
+        "muted": snapshot.muted,
+        "members": members,
+        "mode": snapshot.mode,
+        "text": text,
+        "percentage": volume.unwrap_or(0),
+        "tooltip": format!("{}: {members} members", snapshot.group),
+        "class": if snapshot.muted { json!("muted") } else { json!(snapshot.mode) },
+    })
+}
+
+/// The line for a client following `group`, or every group as an array
+fn line(snapshots: &[Snapshot], group: Option<&str>) -> String {
+    let value = match group {
+        Some(group) => snapshots
+            .iter()
+            .find(|snapshot| snapshot.group == group)
+            .map_or(Value::Null, status),
+        None => snapshots.iter().map(status).collect(),
+    };
+    value.to_string()
+}
+
+impl Monitors {
+    /// Starts sending updates to `stream`, beginning with the current state
+    pub fn add(&self, mut stream: UnixStream, group: Option<String>) -> std::io::Result<()> {
+        let mut clients = self.clients.lock().expect("failed to lock monitors");
+        if let Some(group) = &group {
+            if !clients.snapshots.iter().any(|s| &s.group == group) {
+                return writeln!(stream, "error unknown group: {group}");
+            }
+        }
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let last = line(&clients.snapshots, group.as_deref());
+        writeln!(stream, "ok")?;
+        writeln!(stream, "{last}")?;
+        clients.clients.push(Client {
+            stream,
+            group,
+            last,
+        });
+        Ok(())
+    }
+
+    /// Sends a line to every client whose group changed, dropping clients that went away
+    pub fn update(&self, snapshots: Vec<Snapshot>) {
+        let mut clients = self.clients.lock().expect("failed to lock monitors");
+        if clients.snapshots == snapshots {
+            return;
+        }
+        clients.snapshots = snapshots;
+        let Clients { clients, snapshots } = &mut *clients;
+        clients.retain_mut(|client| {
+            let next = line(snapshots, client.group.as_deref());
+            if next == client.last {
+                return true;
+            }
+            client.last = next;
+            match writeln!(client.stream, "{}", client.last) {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::debug!("dropping monitor client: {e}");
+                    false
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rumqttc::{Client, Event, Incoming, LastWill, MqttOptions, Outgoing, QoS};
+use serde_json::json;
+
+use volume_sync_core::command::Command;
+use volume_sync_core::config::MqttConfig;
+use volume_sync_core::event::VolumeSyncEvent;
+use volume_sync_core::group::{Mode, Snapshot};
+use volume_sync_core::template::TemplateContext;
+use volume_sync_core::volume::{volume_to_percent, VolumeSpec};
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+/// How long to wait before connecting again after the broker went away
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// How long a command from the broker waits for the engine's reply
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What a message on a subscribed topic asks of a group
+#[derive(Clone, Debug)]
+enum Request {
+    /// A volume as taken by `volume-sync set`, e.g. `40` or `+5%`
+    Set(String),
+    /// `ON` or `OFF`, where `OFF` sets the group to 0%
+    Switch(String),
+}
+
+type Subscriptions = Arc<Mutex<BTreeMap<String, Request>>>;
+
+/// Publishes the level and mode of every group to an MQTT broker and sets group volumes from the
+/// messages sent to it. With discovery, Home Assistant shows every group as a dimmable light.
+pub struct Publisher {
+    client: Client,
+    context: TemplateContext,
+    topic: String,
+    status_topic: String,
+    discovery_prefix: String,
+    subscriptions: Subscriptions,
+    /// Set on every connect, as the broker may have lost what was published before, which also
+    /// sends `Republish` so the groups go out again without waiting for a change
+    connected: Arc<AtomicBool>,
+    /// Set once stopping, so a failing connection gives up rather than connecting again
+    stopping: Arc<AtomicBool>,
+    connection: JoinHandle<()>,
+    published: BTreeMap<String, (Option<u32>, Mode)>,
+}
+
+fn mode_name(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Idle => "idle",
+        Mode::Syncing => "syncing",
+        Mode::Paused => "paused",
+    }
+}
+
+/// Passes a request on to the engine as a `set` command, waiting for its reply on a thread of its
+/// own so the connection keeps being polled meanwhile
+fn request(sender: &Sender<VolumeSyncEvent>, request: &Request, payload: &[u8]) {
+    let payload = String::from_utf8_lossy(payload);
+    let payload = payload.trim();
+    let (group, volume) = match request {
+        Request::Set(group) => (group, payload.parse()),
+        Request::Switch(group) => match payload {
+            "ON" => return,
+            "OFF" => (group, Ok(VolumeSpec::Absolute(0))),
+            _ => (group, Err(format!("invalid switch state: {payload}"))),
+        },
+    };
+    let volume = match volume {
+        Ok(volume) => volume,
+        Err(e) => {
+            tracing::warn!(%group, "ignoring MQTT message: {e}");
+            return;
+        }
+    };
+    tracing::info!(%group, "setting volume to {volume} from MQTT");
+    let command = Command::SetVolume {
+        group: group.clone(),
+        volume,
+    };
+    let (reply, replies) = channel();
+    sender
+        .send(VolumeSyncEvent::Command(command, reply))
+        .expect("failed to send command event");
+    let group = group.clone();
+    thread::spawn(move || match replies.recv_timeout(REPLY_TIMEOUT) {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => tracing::warn!(%group, "MQTT volume not set: {e}"),
+        Err(_) => tracing::warn!(%group, "no reply to MQTT volume"),
+    });
+}
+
+impl Publisher {
+    /// Connects to the broker in the background, announcing volume-sync on `status_topic` while
+    /// connected
+    pub fn start(
+        config: &MqttConfig,
+        instance: Option<&str>,
+        sender: Sender<VolumeSyncEvent>,
+    ) -> Result<Publisher, String> {
+        let context = TemplateContext::new(instance);
+        context.with_group("group").render(&config.topic)?;
+        let status_topic = context.render(&config.status_topic)?;
+
+        let mut options = MqttOptions::new(
+            format!("volume-sync-{}", context.instance),
+            &config.host,
+            config.port,
+        );
+        options.set_keep_alive(KEEP_ALIVE);
+        options.set_last_will(LastWill::new(
+            &status_topic,
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.as_deref().unwrap_or(""));
+        }
+        let (client, mut connection) = Client::new(options, 64);
+        let subscriptions = Subscriptions::default();
+        let connected = Arc::new(AtomicBool::new(false));
+        let stopping = Arc::new(AtomicBool::new(false));
+
+        let connection = thread::spawn({
+            let (client, status_topic) = (client.clone(), status_topic.clone());
+            let (subscriptions, connected) = (subscriptions.clone(), connected.clone());
+            let stopping = stopping.clone();
+            move || {
+                for notification in connection.iter() {
+                    match notification {
+                        Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                            tracing::info!("connected to the MQTT broker");
+                            let _ =
+                                client.try_publish(&status_topic, QoS::AtLeastOnce, true, "online");
+                            connected.store(true, Ordering::Relaxed);
+                            let _ = sender.send(VolumeSyncEvent::Republish);
+                        }
+                        Ok(Event::Incoming(Incoming::Publish(publish))) => {
+                            let found = subscriptions
+                                .lock()
+                                .expect("failed to lock MQTT subscriptions")
+                                .get(&publish.topic)
+                                .cloned();
+                            if let Some(found) = found {
+                                request(&sender, &found, &publish.payload);
+                            }
+                        }
+                        Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                        Ok(_) => {}
+                        Err(_) if stopping.load(Ordering::Relaxed) => break,
+                        Err(e) => {
+                            tracing::warn!("MQTT connection failed: {e}");
+                            thread::sleep(RECONNECT_DELAY);
+                        }
+                    }
+                }
+            }
+        });
+        Ok(Publisher {
+            client,
+            context,
+            topic: config.topic.clone(),
+            status_topic,
+            discovery_prefix: config.discovery_prefix.clone(),
+            subscriptions,
+            connected,
+            stopping,
+            connection,
+            published: BTreeMap::new(),
+        })
+    }
+
+    /// Announces volume-sync as offline and disconnects, as the last will only goes out when the
+    /// connection is lost
+    pub fn stop(self) {
+        self.stopping.store(true, Ordering::Relaxed);
+        self.publish(&self.status_topic, "offline");
+        if let Err(e) = self.client.try_disconnect() {
+            tracing::warn!("failed to disconnect from the MQTT broker: {e}");
+            return;
+        }
+        if self.connection.join().is_err() {
+            tracing::warn!("MQTT connection thread panicked");
+        }
+    }
+
+    fn publish(&self, topic: &str, payload: impl Into<Vec<u8>>) {
+        if let Err(e) = self
+            .client
+            .try_publish(topic, QoS::AtLeastOnce, true, payload)
+        {
+            tracing::warn!("failed to publish to {topic}: {e}");
+        }
+    }
+
+    fn base_topic(&self, group: &str) -> String {
+        self.context
+            .with_group(group)
+            .render(&self.topic)
+            .expect("MQTT topic checked at startup")
+    }
+
+    /// Id of a group's entity in Home Assistant, unique across instances
+    fn unique_id(&self, group: &str) -> String {
+        format!("volume_sync_{}_{group}", self.context.instance)
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    }
+
+    fn discovery_topic(&self, group: &str) -> String {
+        format!(
+            "{}/light/{}/config",
+            self.discovery_prefix,
+            self.unique_id(group)
+        )
+    }
+
+    /// Subscribes to the command topics of `group` and publishes its discovery payload
+    fn announce(&self, group: &str) {
+        let base = self.base_topic(group);
+        let mut subscriptions = self
+            .subscriptions
+            .lock()
+            .expect("failed to lock MQTT subscriptions");
+        for (topic, request) in [
+            (format!("{base}/set"), Request::Set(group.to_string())),
+            (format!("{base}/switch"), Request::Switch(group.to_string())),
+        ] {
+            if let Err(e) = self.client.try_subscribe(&topic, QoS::AtLeastOnce) {
+                tracing::warn!("failed to subscribe to {topic}: {e}");
+            }
+            subscriptions.insert(topic, request);
+        }
+        if self.discovery_prefix.is_empty() {
+            return;
+        }
+        let payload = json!({
+            "name": group,
+            "unique_id": self.unique_id(group),
+            "icon": "mdi:volume-high",
+            "availability_topic": self.status_topic,
+            "command_topic": format!("{base}/switch"),
+            "state_topic": format!("{base}/state"),
+            "brightness_command_topic": format!("{base}/set"),
+            "brightness_state_topic": format!("{base}/volume"),
+            "brightness_scale": 100,
+            "on_command_type": "brightness",
+            "device": {
+                "identifiers": [format!("volume_sync_{}", self.context.instance)],
+                "name": format!("volume-sync {}", self.context.instance),
+            },
+        });
+        self.publish(&self.discovery_topic(group), payload.to_string());
+    }
+
+    /// Drops the command topics of a group that is gone and removes it from Home Assistant
+    fn retract(&self, group: &str) {
+        let base = self.base_topic(group);
+        let mut subscriptions = self
+            .subscriptions
+            .lock()
+            .expect("failed to lock MQTT subscriptions");
+        for topic in [format!("{base}/set"), format!("{base}/switch")] {
+            let _ = self.client.try_unsubscribe(&topic);
+            subscriptions.remove(&topic);
+        }
+        if !self.discovery_prefix.is_empty() {
+            self.publish(&self.discovery_topic(group), "");
+        }
+    }
+
+    /// Publishes the level and mode of every group that changed since the last call, announcing
+    /// new groups and retracting those that are gone
+    pub fn update(&mut self, snapshots: &[Snapshot]) {
+        let gone: Vec<String> = self
+            .published
+            .keys()
+            .filter(|group| !snapshots.iter().any(|s| &s.group == *group))
+            .cloned()
+            .collect();
+        for group in gone {
+            self.retract(&group);
+            self.published.remove(&group);
+        }
+        if self.connected.swap(false, Ordering::Relaxed) {
+            self.published.clear();
+        }
+        for snapshot in snapshots {
+            let current = (snapshot.level, snapshot.mode);
+            let previous = self.published.insert(snapshot.group.clone(), current);
+            if previous == Some(current) {
+                continue;
+            }
+            if previous.is_none() {
+                self.announce(&snapshot.group);
+            }
+            let base = self.base_topic(&snapshot.group);
+            self.publish(&format!("{base}/mode"), mode_name(snapshot.mode));
+            if let Some(level) = snapshot.level {
+                let percent = volume_to_percent(level);
+                let state = if percent > 0 { "ON" } else { "OFF" };
+                self.publish(&format!("{base}/volume"), percent.to_string());
+                self.publish(&format!("{base}/state"), state);
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use volume_sync_core::digest::{Category, Digest};
+
+#[cfg(feature = "dbus")]
+#[zbus::proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: std::collections::HashMap<&str, zbus::zvariant::Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+}
+
+enum Message {
+    Configure { enabled: bool, window: u64 },
+    Notify(Category, String),
+}
+
+/// Shows desktop notifications from a background thread, rate limited through a `Digest`
+pub struct Notifier {
+    sender: Sender<Message>,
+}
+
+#[cfg(feature = "dbus")]
+fn show(text: &str) {
+    let result = zbus::blocking::Connection::session()
+        .and_then(|connection| NotificationsProxyBlocking::new(&connection))
+        .and_then(|proxy| {
+            proxy.notify(
+                "volume-sync",
+                0,
+                "audio-volume-medium",
+                "volume-sync",
+                text,
+                &[],
+                Default::default(),
+                -1,
+            )
+        });
+    if let Err(e) = result {
+        tracing::warn!("failed to show notification \"{text}\": {e}");
+    }
+}
+
+#[cfg(not(feature = "dbus"))]
+fn show(text: &str) {
+    tracing::info!("notification: {text}");
+}
+
+impl Notifier {
+    pub fn start() -> Notifier {
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            let start = Instant::now();
+            let mut enabled = false;
+            let mut digest = Digest::new(0);
+            loop {
+                let now = start.elapsed().as_secs();
+                let shown = match receiver.recv_timeout(Duration::from_secs(1)) {
+                    Ok(Message::Configure { enabled: e, window }) => {
+                        enabled = e;
+                        digest.set_window(window);
+                        Vec::new()
+                    }
+                    Ok(Message::Notify(category, message)) if enabled => {
+                        digest.push(now, category, message)
+                    }
+                    Ok(Message::Notify(..)) => Vec::new(),
+                    Err(RecvTimeoutError::Timeout) => digest.flush(now),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                for text in shown {
+                    show(&text);
+                }
+            }
+        });
+        Notifier { sender }
+    }
+
+    pub fn configure(&self, enabled: bool, window: u64) {
+        let _ = self.sender.send(Message::Configure { enabled, window });
+    }
+
+    pub fn notify(&self, category: Category, message: String) {
+        let _ = self.sender.send(Message::Notify(category, message));
+    }
+}
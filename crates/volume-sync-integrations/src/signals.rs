@@ -0,0 +1,19 @@
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+
+use volume_sync_core::event::VolumeSyncEvent;
+
+/// Turns SIGINT and SIGTERM into a shutdown event for the main loop
+pub fn start_listener(sender: Sender<VolumeSyncEvent>) -> std::io::Result<()> {
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            tracing::info!("received signal {signal}, shutting down");
+            let _ = sender.send(VolumeSyncEvent::Shutdown);
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use std::process;
+use std::sync::mpsc::{channel, Sender};
+
+use ksni::blocking::{Handle, TrayMethods};
+use ksni::menu::{CheckmarkItem, StandardItem};
+use ksni::{MenuItem, ToolTip, Tray};
+
+use volume_sync_core::command::Command;
+use volume_sync_core::config::{self, DEFAULT_GROUP};
+use volume_sync_core::event::VolumeSyncEvent;
+use volume_sync_core::group::Snapshot;
+use volume_sync_core::volume::volume_to_percent;
+
+/// Owner of the pause tokens taken from the tray menu
+const OWNER: &str = "tray";
+
+struct Indicator {
+    sender: Sender<VolumeSyncEvent>,
+    snapshots: Vec<Snapshot>,
+}
+
+/// Short state of a group, e.g. `desk: 40%`
+fn label(snapshot: &Snapshot) -> String {
+    let volume = match snapshot.level {
+        _ if snapshot.muted => "muted".to_string(),
+        Some(level) => format!("{}%", volume_to_percent(level)),
+        None => "unknown".to_string(),
+    };
+    format!("{}: {volume}", snapshot.group)
+}
+
+impl Indicator {
+    /// Runs `command` without waiting for its reply, which would hold up the menu
+    fn send(&self, command: Command) {
+        let (reply, _) = channel();
+        self.sender
+            .send(VolumeSyncEvent::Command(command, reply))
+            .expect("failed to send command event");
+    }
+
+    /// The group the icon shows, `default` when there is one
+    fn main_group(&self) -> Option<&Snapshot> {
+        self.snapshots
+            .iter()
+            .find(|snapshot| snapshot.group == DEFAULT_GROUP)
+            .or_else(|| self.snapshots.first())
+    }
+
+    /// Whether the tray holds a pause token on every group
+    fn paused(&self) -> bool {
+        !self.snapshots.is_empty()
+            && self
+                .snapshots
+                .iter()
+                .all(|snapshot| snapshot.holds.contains_key(OWNER))
+    }
+
+    fn toggle_pause(&mut self) {
+        let pause = !self.paused();
+        for snapshot in &self.snapshots {
+            let group = snapshot.group.clone();
+            let owner = OWNER.to_string();
+            self.send(if pause {
+                Command::Pause {
+                    group,
+                    owner,
+                    lease: None,
+                }
+            } else {
+                Command::Resume { group, owner }
+            });
+        }
+    }
+}
+
+impl Tray for Indicator {
+    fn id(&self) -> String {
+        "volume-sync".to_string()
+    }
+
+    fn title(&self) -> String {
+        "volume-sync".to_string()
+    }
+
+    fn icon_name(&self) -> String {
+        let name = match self.main_group() {
+            Some(snapshot) if snapshot.muted => "audio-volume-muted",
+            Some(Snapshot {
+                level: Some(level), ..
+            }) => match volume_to_percent(*level) {
+                0 => "audio-volume-muted",
+                1..=33 => "audio-volume-low",
+                34..=66 => "audio-volume-medium",
+                _ => "audio-volume-high",
+            },
+            _ => "audio-volume-medium",
+        };
+        name.to_string()
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        let mut lines: Vec<String> = self.snapshots.iter().map(label).collect();
+        if self.paused() {
+            lines.push("paused".to_string());
+        }
+        ToolTip {
+            title: "volume-sync".to_string(),
+            description: lines.join("\n"),
+            ..Default::default()
+        }
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let mut items: Vec<MenuItem<Self>> = self
+            .snapshots
+            .iter()
+            .map(|snapshot| {
+                StandardItem {
+                    label: label(snapshot),
+                    enabled: false,
+                    ..Default::default()
+                }
+                .into()
+            })
+            .collect();
+        items.push(MenuItem::Separator);
+        items.push(
+            CheckmarkItem {
+                label: "Pause syncing".to_string(),
+                checked: self.paused(),
+                activate: Box::new(Indicator::toggle_pause),
+                ..Default::default()
+            }
+            .into(),
+        );
+        items.push(
+            StandardItem {
+                label: "Resync".to_string(),
+                activate: Box::new(|this: &mut Indicator| this.send(Command::Resync)),
+                ..Default::default()
+            }
+            .into(),
+        );
+        items.push(
+            StandardItem {
+                label: "Open config".to_string(),
+                activate: Box::new(|_: &mut Indicator| {
+                    let file = config::get_file();
+                    if let Err(e) = process::Command::new("xdg-open").arg(&file).spawn() {
+                        tracing::warn!("failed to open {file}: {e}");
+                    }
+                }),
+                ..Default::default()
+            }
+            .into(),
+        );
+        items
+    }
+}
+
+/// A StatusNotifierItem showing the volume of the groups, with a menu to pause every group, resync
+/// and open the config
+pub struct TrayIcon {
+    handle: Handle<Indicator>,
+    snapshots: Vec<Snapshot>,
+}
+
+impl TrayIcon {
+    pub fn start(sender: Sender<VolumeSyncEvent>) -> Result<TrayIcon, String> {
+        let indicator = Indicator {
+            sender,
+            snapshots: Vec::new(),
+        };
+        let handle = indicator.spawn().map_err(|e| e.to_string())?;
+        Ok(TrayIcon {
+            handle,
+            snapshots: Vec::new(),
+        })
+    }
+
+    /// Shows the state of every group, if it changed
+    pub fn update(&mut self, snapshots: &[Snapshot]) {
+        if self.snapshots == snapshots {
+            return;
+        }
+        self.snapshots = snapshots.to_vec();
+        self.handle.update(|indicator| {
+            indicator.snapshots = snapshots.to_vec();
+        });
+    }
+}
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use volume_sync_core::volume::{VolumeSpec, VOLUME_MAX};
+
+fuzz_target!(|input: (&str, u32)| {
+    let (s, current) = input;
+    if let Ok(spec) = s.parse::<VolumeSpec>() {
+        assert!(spec.apply(current.min(VOLUME_MAX)) <= VOLUME_MAX);
+        assert_eq!(spec.to_string().parse::<VolumeSpec>(), Ok(spec));
+    }
+});
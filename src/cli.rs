@@ -0,0 +1,156 @@
+use volume_sync_core::config;
+use volume_sync_core::engine::Engine;
+use volume_sync_core::journal;
+use volume_sync_core::volume::VolumeSpec;
+use volume_sync_integrations::control;
+
+const USAGE: &str = "usage:
+  volume-sync                      run the daemon
+  volume-sync --dry-run            run the daemon, logging the volumes it would write instead
+  volume-sync --simulate <scenario>
+                                   run the daemon against the scripted sinks of a scenario file
+                                   and print every write it makes
+  volume-sync --check-config       check the config file and its fragments without starting
+  volume-sync set <group> <volume> set every sink in a group, e.g. 40%, +5%, -5%, =-6dB, +3dB
+  volume-sync status               show groups, their members and connection health
+  volume-sync snapshot [group]     print the state of one group or every group as JSON
+  volume-sync list-sinks           list sinks with their description, sample spec and formats
+  volume-sync doctor               check groups and connections for common problems
+  volume-sync profile save <name>  save the volume of every managed sink as a profile
+  volume-sync profile load <name>  set every managed sink back to a saved profile
+  volume-sync profile list         list saved profiles
+  volume-sync pause <group> <owner> [<seconds>]
+                                   stop syncing a group until every owner resumes it or
+                                   their lease runs out
+  volume-sync resume <group> <owner>
+                                   release an owner's pause on a group
+  volume-sync log-level <level>    change the log level, e.g. debug or volume_sync_core=trace,
+                                   until the config is next loaded
+  volume-sync dump --format <dot|mermaid>
+                                   export the groups and their members as a graph
+  volume-sync run <macro>          run every command of a macro from the config
+  volume-sync resync               set every group that isn't paused back to its level
+  volume-sync history [--json]     show the latest volume changes and what triggered them
+  volume-sync calibrate <group>    keep the current offsets between the members of a relative
+                                   group
+  volume-sync resolve <pattern> [--json]
+                                   list the sinks and sources a name pattern matches
+  volume-sync explain <group> <sink> <volume>
+                                   show how a group level is translated for a sink
+  volume-sync monitor [group] [--format json]
+                                   print a line of JSON with the volume, mute and members of
+                                   every group, or one group, whenever it changes
+  volume-sync replay <journal>     replay a recorded journal and print the resulting actions";
+
+fn replay(path: &str) -> i32 {
+    let inputs = match journal::read(path) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return 1;
+        }
+    };
+    let mut engine = Engine::new();
+    for input in inputs {
+        println!("> {input:?}");
+        for action in engine.handle(input) {
+            println!("  {action:?}");
+        }
+    }
+    0
+}
+
+/// Reads the config like the daemon would and reports every problem found
+fn check_config() -> i32 {
+    match config::check_config() {
+        Ok(config) => {
+            let groups: Vec<String> = config.groups().into_keys().collect();
+            println!("{}: ok, groups: {}", config::get_file(), groups.join(", "));
+            0
+        }
+        Err(errors) => {
+            for e in errors {
+                eprintln!("error: {e}");
+            }
+            1
+        }
+    }
+}
+
+/// Streams status lines from the daemon, JSON being the only format so far
+fn monitor(args: &[&str]) -> i32 {
+    let group = match args {
+        [] | ["--format", "json"] => None,
+        [group] | [group, "--format", "json"] => Some(*group),
+        _ => {
+            eprintln!("{USAGE}");
+            return 2;
+        }
+    };
+    let command = match group {
+        Some(group) => format!("monitor {group}"),
+        None => "monitor".to_string(),
+    };
+    match control::follow(&command, std::io::stdout()) {
+        Ok(()) => 0,
+        Err(msg) => {
+            eprintln!("error: {msg}");
+            1
+        }
+    }
+}
+
+pub(crate) fn run(args: &[String]) -> i32 {
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+    let command = match args.as_slice() {
+        ["set", group, volume] => match volume.parse::<VolumeSpec>() {
+            Ok(v) => format!("set {group} {v}"),
+            Err(e) => {
+                eprintln!("{e}");
+                return 2;
+            }
+        },
+        ["status"] => "status".to_string(),
+        ["snapshot"] => "snapshot".to_string(),
+        ["snapshot", group] => format!("snapshot {group}"),
+        ["list-sinks"] => "list-sinks".to_string(),
+        ["doctor"] => "doctor".to_string(),
+        ["profile", "save" | "load", name] => format!("{} {name}", args[..2].join(" ")),
+        ["profile", "list"] => "profile list".to_string(),
+        ["run", name] => format!("run {name}"),
+        ["resync"] => "resync".to_string(),
+        ["history"] => "history".to_string(),
+        ["history", "--json"] => "history --json".to_string(),
+        ["calibrate", group] => format!("calibrate {group}"),
+        ["log-level", level] => format!("log-level {level}"),
+        ["dump", "--format", format] => format!("dump --format {format}"),
+        ["pause", ..] | ["resume", ..] => args.join(" "),
+        ["resolve", pattern] => format!("resolve {pattern}"),
+        ["resolve", pattern, "--json"] => format!("resolve {pattern} --json"),
+        ["explain", group, sink, volume] => format!("explain {group} {sink} {volume}"),
+        ["monitor", rest @ ..] => return monitor(rest),
+        ["replay", path] => return replay(path),
+        ["--check-config"] => return check_config(),
+        ["help"] | ["--help"] | ["-h"] => {
+            println!("{USAGE}");
+            return 0;
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            return 2;
+        }
+    };
+
+    match control::send_command(&command) {
+        Ok(msg) => {
+            if !msg.is_empty() {
+                println!("{msg}");
+            }
+            0
+        }
+        Err(msg) => {
+            eprintln!("error: {msg}");
+            1
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing_subscriber::fmt::MakeWriter;
+
+use volume_sync_core::config::{Config, LogRotation};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+struct Output {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    /// Day the file was opened, for daily rotation
+    day: u64,
+    rotation: LogRotation,
+    max_size: u64,
+    keep: usize,
+}
+
+/// Log output to a file that can be set or changed on every config load, rotated by size or daily
+/// and keeping `log_keep` old files next to it as `<file>.1`, `<file>.2`, ...
+#[derive(Clone, Default)]
+pub struct LogFile {
+    output: Arc<Mutex<Option<Output>>>,
+}
+
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() / SECONDS_PER_DAY)
+}
+
+/// Expands a leading `~/` to the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl Output {
+    fn needs_rotation(&self, len: usize) -> bool {
+        match self.rotation {
+            LogRotation::Never => false,
+            LogRotation::Size => self.size > 0 && self.size + len as u64 > self.max_size,
+            LogRotation::Daily => self.day != today(),
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..self.keep).rev() {
+            let from = rotated(&self.path, n);
+            if from.exists() {
+                fs::rename(from, rotated(&self.path, n + 1))?;
+            }
+        }
+        if self.keep > 0 {
+            fs::rename(&self.path, rotated(&self.path, 1))?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+        self.file = open(&self.path)?;
+        self.size = 0;
+        self.day = today();
+        Ok(())
+    }
+}
+
+impl LogFile {
+    /// Starts, moves or stops logging to the file set in `config`
+    pub fn configure(&self, config: &Config) {
+        let mut output = self.output.lock().expect("failed to lock log file");
+        let Some(path) = &config.log_file else {
+            *output = None;
+            return;
+        };
+        let path = expand_home(path);
+        let settings = |o: &mut Output| {
+            o.rotation = config.log_rotation;
+            o.max_size = config.log_max_size.get().saturating_mul(1024 * 1024);
+            o.keep = config.log_keep;
+        };
+        if let Some(o) = output.as_mut().filter(|o| o.path == path) {
+            settings(o);
+            return;
+        }
+        match open(&path) {
+            Ok(file) => {
+                let size = file.metadata().map_or(0, |m| m.len());
+                let mut o = Output {
+                    path,
+                    file,
+                    size,
+                    day: today(),
+                    rotation: LogRotation::default(),
+                    max_size: 0,
+                    keep: 0,
+                };
+                settings(&mut o);
+                *output = Some(o);
+            }
+            Err(e) => {
+                *output = None;
+                eprintln!("failed to open log file {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut output = self.output.lock().expect("failed to lock log file");
+        let Some(o) = output.as_mut() else {
+            return Ok(buf.len());
+        };
+        if o.needs_rotation(buf.len()) {
+            if let Err(e) = o.rotate() {
+                // Trying again on every line would only fail the same way, so rotation stays off
+                // until the config is loaded again
+                o.rotation = LogRotation::Never;
+                eprintln!(
+                    "failed to rotate log file {}, no longer rotating it: {e}",
+                    o.path.display()
+                );
+            }
+        }
+        let written = o.file.write(buf)?;
+        o.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self
+            .output
+            .lock()
+            .expect("failed to lock log file")
+            .as_mut()
+        {
+            Some(o) => o.file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogFile {
+    type Writer = LogFile;
+
+    fn make_writer(&'a self) -> LogFile {
+        self.clone()
+    }
+}
@@ -1,145 +1,738 @@
-use std::cell::RefCell;
-use std::collections::HashSet;
-use std::path::Path;
-use std::rc::Rc;
-use std::sync::mpsc::channel;
-use std::sync::{Arc, Mutex};
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use closure::closure;
 
-use notify::event::{ModifyKind, RemoveKind, RenameMode};
-use notify::{EventKind, RecursiveMode, Watcher};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{reload, EnvFilter, Registry};
 
-use pulse::mainloop::threaded::Mainloop;
+#[cfg(feature = "alsa")]
+use volume_sync_backend_alsa::mixers::Mixers;
+#[cfg(feature = "jack")]
+use volume_sync_backend_jack::gains::Gains;
+#[cfg(feature = "pulse")]
+use volume_sync_backend_pulse::contexts::Contexts;
 
-use simple_logger::SimpleLogger;
+use volume_sync_core::backend::Backend;
+use volume_sync_core::command::Reply;
+use volume_sync_core::config::{BackendKind, Config, LogFilter, LogLevel};
+use volume_sync_core::engine::{Action, Engine, Input};
+use volume_sync_core::event::VolumeSyncEvent;
+use volume_sync_core::format;
+use volume_sync_core::history;
+use volume_sync_core::journal::Journal;
+use volume_sync_core::sink::{DeviceId, DeviceKind, SinkDetails};
+use volume_sync_core::state::{self, State};
 
-mod config;
-use crate::config::{Config, LogLevel};
+#[cfg(feature = "http")]
+use volume_sync_integrations::http;
+use volume_sync_integrations::monitor::Monitors;
+#[cfg(feature = "mqtt")]
+use volume_sync_integrations::mqtt::Publisher;
+use volume_sync_integrations::notifications::Notifier;
+#[cfg(feature = "tray")]
+use volume_sync_integrations::tray::TrayIcon;
+use volume_sync_integrations::{control, signals};
+#[cfg(feature = "dbus")]
+use volume_sync_integrations::{gamemode, logind};
 
-mod volume_sync;
-use crate::volume_sync::{VolumeSync, VolumeSyncEvent};
+mod cli;
+mod log_file;
+mod reloader;
+mod simulate;
+mod watcher;
 
-fn main() {
-    SimpleLogger::new().init().unwrap();
-    log::set_max_level(log::LevelFilter::Info);
+use log_file::LogFile;
+use reloader::{read_config, Reloader};
+use simulate::{Scenario, Simulation};
 
-    let sink_indices = Arc::new(Mutex::new(HashSet::new()));
-    let config = Arc::new(Mutex::new(Config::default()));
-    let (sender, receiver) = channel();
+use watcher::ConfigWatcher;
 
-    let handle_config_change = |c: Config| {
-        let log_level = c.log_level.unwrap_or(LogLevel::Info);
-        *config.lock().unwrap() = Config {
-            log_level: Some(log_level.clone()),
-            sinks: c.sinks.clone(),
-        };
-        log::debug!("new config: {:?}", config.lock().unwrap());
-        log::info!("set log_level to {log_level:?}");
-        log::set_max_level(log_level.to_level_filter());
+/// Changes the log filter of the running subscriber
+type LogLevelHandle = reload::Handle<EnvFilter, Registry>;
+
+fn set_log_filter(log_level_handle: &LogLevelHandle, directives: &str) -> Result<(), String> {
+    let filter = EnvFilter::builder()
+        .parse(directives)
+        .map_err(|e| format!("invalid log level {directives}: {e}"))?;
+    log_level_handle
+        .reload(filter)
+        .map_err(|e| format!("failed to set log level: {e}"))?;
+    tracing::info!("set log_level to {directives}");
+    Ok(())
+}
+
+/// Applies the logging settings of a freshly read config
+fn apply_logging(c: Config, log_level_handle: &LogLevelHandle, log_file: &LogFile) -> Config {
+    log_file.configure(&c);
+    let log_level = c
+        .log_level
+        .clone()
+        .unwrap_or(LogFilter::Level(LogLevel::Info));
+    tracing::debug!("new config: {c:?}");
+    if let Err(e) = set_log_filter(log_level_handle, &log_level.directives()) {
+        tracing::warn!("{e}");
+    }
+    Config {
+        log_level: Some(log_level),
+        ..c
+    }
+}
+
+/// Asks the event loop to look for new devices while idle, when no server events are received
+struct IdlePoller {
+    idle: Arc<AtomicBool>,
+    interval: Arc<AtomicU64>,
+}
+
+impl IdlePoller {
+    fn start(sender: Sender<VolumeSyncEvent>) -> IdlePoller {
+        let idle = Arc::new(AtomicBool::new(false));
+        let interval = Arc::new(AtomicU64::new(10));
+        thread::spawn(closure!(clone idle, clone interval, || loop {
+            thread::sleep(Duration::from_secs(interval.load(Ordering::Relaxed).max(1)));
+            if idle.load(Ordering::Relaxed) {
+                sender
+                    .send(VolumeSyncEvent::IdlePoll)
+                    .expect("failed to send idle poll event");
+            }
+        }));
+        IdlePoller { idle, interval }
+    }
+}
+
+/// Sends a tick every second
+fn start_clock(sender: Sender<VolumeSyncEvent>) {
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        if sender.send(VolumeSyncEvent::Tick).is_err() {
+            return;
+        }
+    });
+}
+
+/// Seconds since the epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Periodically asks the event loop to check the topology model against the server
+fn start_validation(sender: Sender<VolumeSyncEvent>) -> Arc<AtomicU64> {
+    let interval = Arc::new(AtomicU64::new(0));
+    thread::spawn(closure!(clone interval, || loop {
+        let seconds = interval.load(Ordering::Relaxed);
+        thread::sleep(Duration::from_secs(seconds.max(1)));
+        if seconds > 0 {
+            sender
+                .send(VolumeSyncEvent::Refresh)
+                .expect("failed to send refresh event");
+        }
+    }));
+    interval
+}
+
+/// How the actions of the engine are carried out
+#[derive(Clone, Copy, PartialEq)]
+enum RunMode {
+    Live,
+    /// Only log the writes
+    DryRun,
+    /// Write to a simulated backend, leaving the state and history files alone
+    Simulate,
+}
+
+/// What a write would have done, for actions left out in a dry run
+fn dry_run(action: &Action) -> Option<String> {
+    let join = |devices: &[DeviceId]| {
+        let devices: Vec<String> = devices.iter().map(DeviceId::to_string).collect();
+        devices.join(", ")
     };
+    let message = match action {
+        Action::SyncVolume {
+            group, from, to, ..
+        } => {
+            format!("{group}: would copy the volume of {from} to {}", join(to))
+        }
+        Action::SetVolume {
+            group,
+            devices,
+            volume,
+        } => format!("{group}: would set {} to {volume}", join(devices)),
+        Action::RestoreVolume {
+            group,
+            device,
+            volume,
+        } => format!("{group}: would set {device} to {}", format::volume(*volume)),
+        Action::SaveState(_) => "would save the state".to_string(),
+        Action::Combine { group, sinks } if sinks.is_empty() => {
+            format!("{group}: would remove the combine-sink")
+        }
+        Action::Combine { group, sinks } => format!("{group}: would combine {}", sinks.join(", ")),
+        Action::SetDefaultSink(name) => format!("would make {name} the default sink"),
+        _ => return None,
+    };
+    Some(message)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute(
+    backend: &dyn Backend,
+    sender: &Sender<VolumeSyncEvent>,
+    poller: &IdlePoller,
+    notifier: &Notifier,
+    log_level_handle: &LogLevelHandle,
+    actions: Vec<Action>,
+    reply: Option<&Sender<Reply>>,
+    mode: RunMode,
+) -> u64 {
+    let mut failed_replies = 0;
+    for action in actions {
+        tracing::debug!("action: {action:?}");
+        if mode == RunMode::DryRun {
+            if let Some(message) = dry_run(&action) {
+                tracing::info!("dry run: {message}");
+                continue;
+            }
+        }
+        if mode == RunMode::Simulate
+            && matches!(action, Action::SaveState(_) | Action::AppendHistory { .. })
+        {
+            continue;
+        }
+        match action {
+            Action::SyncVolume {
+                group,
+                from,
+                to,
+                min_delta,
+            } => {
+                let _span = tracing::info_span!("sync", %group).entered();
+                backend.sync_volume(&group, from, &to, min_delta)
+            }
+            Action::SetVolume {
+                group,
+                devices,
+                volume,
+            } => backend.set_volume(&group, &devices, volume),
+            Action::RestoreVolume {
+                group,
+                device,
+                volume,
+            } => backend.restore_volume(&group, device, volume),
+            Action::SaveState(s) => {
+                if let Err(e) = state::save_state(&s) {
+                    tracing::warn!("failed to save state to {}: {e}", state::get_file());
+                }
+            }
+            Action::SetIdle(idle) => {
+                tracing::info!("idle: {idle}");
+                backend.set_subscribed(!idle);
+                poller.idle.store(idle, Ordering::Relaxed);
+            }
+            Action::Notify { category, message } => notifier.notify(category, message),
+            Action::Combine { group, sinks } => backend.combine(&group, &sinks),
+            Action::SetDefaultSink(name) => backend.set_default_sink(&name),
+            Action::AppendHistory { file, changes } => {
+                if let Err(e) = history::append(&file, &changes) {
+                    tracing::warn!("failed to write history to {file}: {e}");
+                }
+            }
+            Action::Refresh => sender
+                .send(VolumeSyncEvent::Refresh)
+                .expect("failed to send refresh event"),
+            Action::SetLogFilter(directives) => {
+                let result = set_log_filter(log_level_handle, &directives).map(|()| {
+                    format!("log level set to {directives} until the config is next loaded")
+                });
+                if let Some(sender) = reply {
+                    if sender.send(result).is_err() {
+                        failed_replies += 1;
+                    }
+                }
+            }
+            Action::Reply(r) => {
+                if let Some(sender) = reply {
+                    if sender.send(r).is_err() {
+                        failed_replies += 1;
+                    }
+                }
+            }
+        }
+    }
+    failed_replies
+}
 
-    if let Some(c) = config::load_config() {
-        handle_config_change(c);
+/// Collects the changes received within `window` after `first`, keeping only the latest change of
+/// each device; stops early at any other event, which is returned to be handled next
+fn collect_changes(
+    first: SinkDetails,
+    receiver: &Receiver<VolumeSyncEvent>,
+    window: Duration,
+) -> (Input, Option<VolumeSyncEvent>) {
+    let deadline = Instant::now() + window;
+    let mut changes = vec![first];
+    let mut next = None;
+    while let Ok(event) = receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+    {
+        match event {
+            VolumeSyncEvent::SinkChanged(device)
+            | VolumeSyncEvent::SourceChanged(device)
+            | VolumeSyncEvent::RecordingChanged(device) => {
+                changes.retain(|change| change.id() != device.id());
+                changes.push(device);
+            }
+            event => {
+                next = Some(event);
+                break;
+            }
+        }
+    }
+    let input = if changes.len() == 1 {
+        let device = changes.remove(0);
+        match device.kind {
+            DeviceKind::Sink => Input::SinkChanged(device),
+            DeviceKind::Source => Input::SourceChanged(device),
+            DeviceKind::Recording => Input::RecordingChanged(device),
+        }
     } else {
-        log::warn!("no config file found: {}", config::get_file());
-        handle_config_change(Config::default());
+        Input::Changes(changes)
     };
+    (input, next)
+}
 
-    let mut watcher = notify::recommended_watcher(closure!(
-        clone sender,
-        |res: notify::Result<notify::Event>| match res {
-            Ok(event) => {
-                if event.paths.first().map_or("", |p| p.to_str().expect("failed to get event path")) == config::get_file() {
-                    match event.kind {
-                        EventKind::Modify(ModifyKind::Name(RenameMode::To))
-                        | EventKind::Modify(ModifyKind::Name(RenameMode::From))
-                        | EventKind::Modify(ModifyKind::Data(_))
-                        | EventKind::Remove(RemoveKind::File) => {
-                            log::info!("event: {event:?}");
-                            sender.send(VolumeSyncEvent::ConfigChanged).expect("failed to send config event");
-                        }
-                        _ => log::debug!("ignore event: {event:?}"),
+/// Connects to the sound system chosen in `config`
+fn connect_backend(
+    config: &Config,
+    sender: Sender<VolumeSyncEvent>,
+) -> Result<Box<dyn Backend>, String> {
+    if config.server.is_some() && config.backend != BackendKind::Pulse {
+        tracing::warn!("server is only used by the pulse backend");
+    }
+    match config.backend {
+        #[cfg(feature = "pulse")]
+        BackendKind::Pulse => Ok(Box::new(Contexts::connect(
+            sender,
+            config.server.as_deref(),
+        )?)),
+        #[cfg(feature = "alsa")]
+        BackendKind::Alsa => Ok(Box::new(Mixers::connect(sender)?)),
+        #[cfg(feature = "jack")]
+        BackendKind::Jack => Ok(Box::new(Gains::connect(sender, config)?)),
+        #[allow(unreachable_patterns)]
+        kind => {
+            drop(sender);
+            Err(format!("built without the {kind:?} backend"))
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let dry_run_flag = args == ["--dry-run"];
+    let scenario = match args.as_slice() {
+        [flag, path] if flag == "--simulate" => match Scenario::read(path) {
+            Ok(scenario) => Some(scenario),
+            Err(e) => {
+                eprintln!("error: {e}");
+                process::exit(1);
+            }
+        },
+        _ => None,
+    };
+    if !args.is_empty() && !dry_run_flag && scenario.is_none() {
+        process::exit(cli::run(&args));
+    }
+
+    let (level_filter, log_level_handle) = reload::Layer::new(EnvFilter::new("info"));
+    let log_file = LogFile::default();
+    tracing_subscriber::registry()
+        .with(level_filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(log_file.clone()),
+        )
+        .init();
+
+    // A simulation runs the config of its scenario and leaves the control socket and config file to
+    // the daemon that may be running
+    let simulating = scenario.is_some();
+    let mut scenario = scenario;
+    let config = match scenario
+        .as_mut()
+        .and_then(|scenario| scenario.config.take())
+    {
+        Some(config) => config,
+        None => read_config(),
+    };
+    let initial_config = apply_logging(config, &log_level_handle, &log_file);
+    let engine = RefCell::new(Engine::new());
+    let mut journal = Journal::new();
+    let (sender, receiver) = channel();
+    let mut reloader = (!simulating).then(|| Reloader::new(sender.clone(), &initial_config));
+    let poller = IdlePoller::start(sender.clone());
+    let notifier = Notifier::start();
+    let monitors = Monitors::default();
+    let validation_interval = start_validation(sender.clone());
+    start_clock(sender.clone());
+    let shutdown_timeout = Cell::new(initial_config.shutdown_timeout);
+    let arbitration_window = Cell::new(initial_config.arbitration_window);
+    let defer_during_performance = Cell::new(initial_config.defer_during_performance);
+    let run_mode = Cell::new(match scenario {
+        Some(_) => RunMode::Simulate,
+        None => RunMode::Live,
+    });
+    #[cfg(feature = "mqtt")]
+    let publisher = RefCell::new(initial_config.mqtt.as_ref().and_then(|mqtt| {
+        tracing::info!("starting MQTT publisher for {}", mqtt.host);
+        Publisher::start(mqtt, initial_config.instance.as_deref(), sender.clone())
+            .inspect_err(|e| tracing::warn!("failed to start MQTT publisher: {e}"))
+            .ok()
+    }));
+    #[cfg(not(feature = "mqtt"))]
+    if initial_config.mqtt.is_some() {
+        tracing::warn!("built without the mqtt feature, not publishing to MQTT");
+    }
+    #[cfg(feature = "tray")]
+    let mut tray = TrayIcon::start(sender.clone())
+        .inspect_err(|e| tracing::warn!("failed to show tray icon: {e}"))
+        .ok();
+
+    let mut handle =
+        |input: Input, backend: &mut Box<dyn Backend>, reply: Option<&Sender<Reply>>| {
+            if let Input::ConfigLoaded(c) | Input::ConfigReloaded { config: c, .. } = &input {
+                journal.set_path(c.journal.clone());
+                backend.configure(&c.groups());
+                poller
+                    .interval
+                    .store(c.idle_poll_interval, Ordering::Relaxed);
+                shutdown_timeout.set(c.shutdown_timeout);
+                arbitration_window.set(c.arbitration_window);
+                defer_during_performance.set(c.defer_during_performance);
+                notifier.configure(c.notifications, c.notification_window);
+                validation_interval.store(c.validation_interval, Ordering::Relaxed);
+                format::set_reference(c.percent_reference);
+                let mode = match run_mode.get() {
+                    RunMode::Simulate => RunMode::Simulate,
+                    _ if dry_run_flag || c.dry_run => RunMode::DryRun,
+                    _ => RunMode::Live,
+                };
+                if mode != run_mode.replace(mode) {
+                    if mode == RunMode::DryRun {
+                        tracing::warn!("dry run: logging volumes instead of writing them");
+                    } else {
+                        tracing::info!("dry run over, writing volumes again");
                     }
                 }
             }
-            Err(e) => log::error!("error: {e:?}"),
+            if let Input::Command(_) = &input {
+                // Leases taken by a command count from its own time, also when replayed
+                let tick = Input::Tick(now());
+                journal.record(&tick);
+                let actions = engine.borrow_mut().handle(tick);
+                execute(
+                    backend.as_ref(),
+                    &sender,
+                    &poller,
+                    &notifier,
+                    &log_level_handle,
+                    actions,
+                    None,
+                    run_mode.get(),
+                );
+            }
+            // Other ticks only matter to replays while a lease can expire
+            if !matches!(input, Input::Tick(_)) || engine.borrow().has_leases() {
+                journal.record(&input);
+            }
+            if let Input::Shutdown = input {
+                journal.flush();
+            }
+            let actions = engine.borrow_mut().handle(input);
+            let failed_replies = execute(
+                backend.as_ref(),
+                &sender,
+                &poller,
+                &notifier,
+                &log_level_handle,
+                actions,
+                reply,
+                run_mode.get(),
+            );
+            let mut engine = engine.borrow_mut();
+            engine.count_channel_errors(failed_replies);
+            let snapshots = engine.snapshots();
+            #[cfg(feature = "mqtt")]
+            if let Some(publisher) = publisher.borrow_mut().as_mut() {
+                publisher.update(&snapshots);
+            }
+            #[cfg(feature = "tray")]
+            if let Some(tray) = &mut tray {
+                tray.update(&snapshots);
+            }
+            monitors.update(snapshots);
+        };
+
+    let mut watcher = (!simulating).then(|| {
+        tracing::info!("starting config file watcher");
+        ConfigWatcher::start(sender.clone()).expect("failed to start config file watcher")
+    });
+
+    if !simulating {
+        tracing::info!("starting control socket");
+        control::start_listener(sender.clone(), monitors.clone())
+            .expect("failed to start control socket");
+    }
+
+    if let Some(address) = &initial_config.http_listen {
+        #[cfg(feature = "http")]
+        if let Err(e) = http::start_listener(address, sender.clone()) {
+            tracing::warn!("failed to start HTTP API: {e}");
         }
-    )).expect("failed to create config file watcher");
-    log::info!("starting config file watcher");
-    watcher
-        .watch(
-            Path::new(&config::get_file())
-                .parent()
-                .expect("no parent dir"),
-            RecursiveMode::NonRecursive,
-        )
-        .expect("failed to start config file watcher");
-
-    let mainloop = Rc::new(RefCell::new(
-        Mainloop::new().expect("failed to create mainloop"),
-    ));
-    let volume_sync = Rc::new(RefCell::new(VolumeSync::new(mainloop.clone(), sender)));
-
-    log::info!("starting mainloop");
-    mainloop.borrow_mut().lock();
-    mainloop
-        .borrow_mut()
-        .start()
-        .expect("failed to start mainloop");
-    mainloop.borrow_mut().unlock();
-    volume_sync
-        .borrow_mut()
-        .connect()
-        .expect("failed to connect volume_sync");
-
-    let update_sink_indices = || {
-        let sinks = volume_sync.borrow().get_sinks();
-        let cfg = config.lock().unwrap();
-        let mut indices = sink_indices.lock().unwrap();
-        indices.clear();
-        for sink in sinks {
-            if cfg.sinks.contains(&sink.name) {
-                indices.insert(sink.index);
+        #[cfg(not(feature = "http"))]
+        tracing::warn!("built without the http feature, not serving {address}");
+    }
+
+    tracing::info!("starting signal listener");
+    signals::start_listener(sender.clone()).expect("failed to start signal listener");
+
+    #[cfg(feature = "dbus")]
+    if !simulating {
+        tracing::info!("starting logind sleep listener");
+        if let Err(e) = logind::start_listener(sender.clone()) {
+            tracing::warn!("failed to watch logind for resume: {e}");
+        }
+        tracing::info!("starting gamemode listener");
+        if let Err(e) = gamemode::start_listener(sender.clone()) {
+            tracing::info!("gamemode not available: {e}");
+        }
+        if let Some(signal) = &initial_config.performance_signal {
+            tracing::info!("starting {signal} listener");
+            if let Err(e) = gamemode::start_signal_listener(sender.clone(), signal) {
+                tracing::warn!("failed to watch {signal}: {e}");
             }
         }
+    }
+
+    let mut backend = match scenario {
+        Some(scenario) => Box::new(Simulation::start(scenario, sender.clone())),
+        None => {
+            connect_backend(&initial_config, sender.clone()).expect("failed to connect backend")
+        }
     };
-    update_sink_indices();
 
+    handle(
+        Input::ConfigLoaded(Box::new(initial_config)),
+        &mut backend,
+        None,
+    );
+    handle(Input::Tick(now()), &mut backend, None);
+    if let Some(watcher) = &watcher {
+        handle(Input::WatcherHealth(watcher.health()), &mut backend, None);
+    }
+    let saved = if simulating {
+        State::default()
+    } else {
+        state::load_state()
+    };
+    handle(Input::StateLoaded(saved), &mut backend, None);
+    backend.request_topology();
+
+    let mut next = None;
+    let mut resume_pending = false;
+    let mut performance = false;
+    let mut deferred = false;
     loop {
-        log::debug!("waiting for event");
-        match receiver.recv() {
-            Ok(e) => match &e {
+        tracing::debug!("waiting for event");
+        let event = match next.take() {
+            Some(event) => Ok(event),
+            None => receiver.recv(),
+        };
+        match event {
+            Ok(e) => match e {
+                VolumeSyncEvent::SinkChanged(device)
+                | VolumeSyncEvent::SourceChanged(device)
+                | VolumeSyncEvent::RecordingChanged(device)
+                    if arbitration_window.get() > 0 =>
+                {
+                    let window = Duration::from_millis(arbitration_window.get());
+                    let (input, event) = collect_changes(device, &receiver, window);
+                    next = event;
+                    handle(input, &mut backend, None);
+                }
                 VolumeSyncEvent::SinkNew(sink) => {
-                    let names = &config.lock().unwrap().sinks;
-                    let mut indices = sink_indices.lock().unwrap();
-                    if names.contains(&sink.name) {
-                        indices.insert(sink.index);
-                    }
+                    handle(Input::SinkNew(sink), &mut backend, None);
                 }
                 VolumeSyncEvent::SinkChanged(index) => {
-                    let indices = sink_indices.lock().unwrap();
-                    if indices.contains(&index) {
-                        for i in indices.iter() {
-                            volume_sync.borrow().sync_volume(*index, *i);
-                        }
-                    }
+                    handle(Input::SinkChanged(index), &mut backend, None);
                 }
                 VolumeSyncEvent::SinkRemoved(index) => {
-                    sink_indices.lock().unwrap().remove(index);
+                    handle(Input::SinkRemoved(index), &mut backend, None);
+                }
+                VolumeSyncEvent::SourceNew(source) => {
+                    handle(Input::SourceNew(source), &mut backend, None);
+                }
+                VolumeSyncEvent::SourceChanged(source) => {
+                    handle(Input::SourceChanged(source), &mut backend, None);
+                }
+                VolumeSyncEvent::SourceRemoved(index) => {
+                    handle(Input::SourceRemoved(index), &mut backend, None);
+                }
+                VolumeSyncEvent::RecordingNew(recording) => {
+                    handle(Input::RecordingNew(recording), &mut backend, None);
+                }
+                VolumeSyncEvent::RecordingChanged(recording) => {
+                    handle(Input::RecordingChanged(recording), &mut backend, None);
+                }
+                VolumeSyncEvent::RecordingRemoved(index) => {
+                    handle(Input::RecordingRemoved(index), &mut backend, None);
+                }
+                VolumeSyncEvent::CardChanged(card) => {
+                    handle(Input::CardChanged(card), &mut backend, None);
+                }
+                VolumeSyncEvent::CardRemoved(index) => {
+                    handle(Input::CardRemoved(index), &mut backend, None);
+                }
+                VolumeSyncEvent::StreamChanged(stream) => {
+                    handle(Input::StreamChanged(stream), &mut backend, None);
+                }
+                VolumeSyncEvent::StreamRemoved(index) => {
+                    handle(Input::StreamRemoved(index), &mut backend, None);
+                }
+                VolumeSyncEvent::CardListLoaded(cards) => {
+                    handle(Input::CardList(cards), &mut backend, None);
+                }
+                VolumeSyncEvent::SinkListLoaded(sinks) => {
+                    handle(Input::SinkList(sinks), &mut backend, None);
+                }
+                VolumeSyncEvent::SourceListLoaded(sources) => {
+                    handle(Input::SourceList(sources), &mut backend, None);
+                }
+                VolumeSyncEvent::RecordingListLoaded(recordings) => {
+                    handle(Input::RecordingList(recordings), &mut backend, None);
+                }
+                VolumeSyncEvent::StreamListLoaded(streams) => {
+                    handle(Input::StreamList(streams), &mut backend, None);
+                    if resume_pending {
+                        resume_pending = false;
+                        handle(Input::Resumed, &mut backend, None);
+                    }
+                }
+                VolumeSyncEvent::Tick => {
+                    handle(Input::Tick(now()), &mut backend, None);
+                }
+                VolumeSyncEvent::Refresh | VolumeSyncEvent::IdlePoll
+                    if performance && defer_during_performance.get() =>
+                {
+                    tracing::debug!("deferring topology check during performance mode");
+                    deferred = true;
+                }
+                VolumeSyncEvent::Refresh | VolumeSyncEvent::IdlePoll => {
+                    backend.request_topology();
+                }
+                VolumeSyncEvent::Performance(active) => {
+                    if active == performance {
+                        continue;
+                    }
+                    performance = active;
+                    handle(Input::Performance(active), &mut backend, None);
+                    if !active && deferred {
+                        tracing::info!("performance mode ended, running deferred work");
+                        deferred = false;
+                        backend.request_topology();
+                    }
+                }
+                VolumeSyncEvent::Resumed => {
+                    backend.request_topology();
+                    resume_pending = true;
                 }
                 VolumeSyncEvent::ConfigChanged => {
-                    if let Some(c) = config::load_config() {
-                        handle_config_change(c);
-                    } else {
-                        log::warn!("no config file found: {}", config::get_file());
-                        handle_config_change(Config::default());
+                    if let (Some(watcher), Some(reloader)) = (&mut watcher, &mut reloader) {
+                        watcher.refresh();
+                        reloader.request(&engine.borrow());
+                    }
+                }
+                VolumeSyncEvent::ConfigReady {
+                    config,
+                    members,
+                    generation,
+                } => {
+                    let again = reloader
+                        .as_mut()
+                        .is_some_and(|reloader| reloader.finished(&config));
+                    let config = apply_logging(*config, &log_level_handle, &log_file);
+                    let input = Input::ConfigReloaded {
+                        config: Box::new(config),
+                        members,
+                        generation,
                     };
-                    log::debug!("fetch sinks");
-                    update_sink_indices();
+                    handle(input, &mut backend, None);
+                    backend.request_topology();
+                    if let Some(reloader) = reloader.as_mut().filter(|_| again) {
+                        reloader.request(&engine.borrow());
+                    }
+                }
+                VolumeSyncEvent::ConfigRejected => {
+                    if let Some(reloader) = &mut reloader {
+                        if reloader.rejected() {
+                            reloader.request(&engine.borrow());
+                        }
+                    }
+                }
+                VolumeSyncEvent::WatcherError(e) => {
+                    if let Some(watcher) = &mut watcher {
+                        watcher.restart(e);
+                        handle(Input::WatcherHealth(watcher.health()), &mut backend, None);
+                    }
+                }
+                VolumeSyncEvent::ContextState(context, state) => {
+                    handle(Input::ContextState { context, state }, &mut backend, None);
+                }
+                VolumeSyncEvent::Republish => {
+                    #[cfg(feature = "mqtt")]
+                    if let Some(publisher) = publisher.borrow_mut().as_mut() {
+                        publisher.update(&engine.borrow().snapshots());
+                    }
+                }
+                VolumeSyncEvent::Command(command, reply) => {
+                    handle(Input::Command(command), &mut backend, Some(&reply));
+                }
+                VolumeSyncEvent::Shutdown => {
+                    let timeout = Duration::from_secs(shutdown_timeout.get());
+                    thread::spawn(move || {
+                        thread::sleep(timeout);
+                        tracing::error!(
+                            "shutdown did not finish within {timeout:?}, exiting anyway"
+                        );
+                        process::exit(1);
+                    });
+                    if !simulating {
+                        tracing::info!("stopping control socket");
+                        control::stop_listener();
+                    }
+                    #[cfg(feature = "http")]
+                    http::stop_listener();
+                    #[cfg(feature = "mqtt")]
+                    if let Some(publisher) = publisher.borrow_mut().take() {
+                        tracing::info!("stopping MQTT publisher");
+                        publisher.stop();
+                    }
+                    handle(Input::Shutdown, &mut backend, None);
+                    backend.shutdown();
+                    tracing::info!("shutdown complete");
+                    return;
                 }
             },
-            Err(err) => log::warn!("error in receiver: {}", err),
+            Err(err) => tracing::warn!("error in receiver: {}", err),
         }
     }
 }
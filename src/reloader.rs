@@ -0,0 +1,145 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use closure::closure;
+
+use volume_sync_core::config::{self, Config, GroupConfig};
+use volume_sync_core::engine::Engine;
+use volume_sync_core::event::VolumeSyncEvent;
+use volume_sync_core::group::{self, Members};
+
+/// Reads and parses the config, falling back to the defaults when there is none
+fn load() -> Result<Config, Vec<String>> {
+    let config = config::load_config()?.unwrap_or_else(|| {
+        tracing::warn!("no config file found: {}", config::get_file());
+        Config::default()
+    });
+    Ok(config.with_env_overrides())
+}
+
+fn log_invalid(errors: &[String]) {
+    for e in errors {
+        tracing::error!("invalid config: {e}");
+    }
+}
+
+/// Reads the config at startup, falling back to the defaults when it is invalid
+pub fn read_config() -> Config {
+    load().unwrap_or_else(|errors| {
+        log_invalid(&errors);
+        tracing::warn!("falling back to the default config");
+        Config::default().with_env_overrides()
+    })
+}
+
+/// Names of the groups added, removed and changed between two configs
+fn diff(old: &BTreeMap<String, GroupConfig>, new: &BTreeMap<String, GroupConfig>) -> String {
+    let added: Vec<&str> = new
+        .keys()
+        .filter(|g| !old.contains_key(*g))
+        .map(|g| g.as_str())
+        .collect();
+    let removed: Vec<&str> = old
+        .keys()
+        .filter(|g| !new.contains_key(*g))
+        .map(|g| g.as_str())
+        .collect();
+    let changed: Vec<&str> = new
+        .iter()
+        .filter(|(g, c)| old.get(*g).is_some_and(|o| o != *c))
+        .map(|(g, _)| g.as_str())
+        .collect();
+    format!(
+        "added [{}], removed [{}], changed [{}]",
+        added.join(", "),
+        removed.join(", "),
+        changed.join(", ")
+    )
+}
+
+/// Members each group gains and loses between two memberships, e.g. `desk: +sink 3, -sink 1`
+fn membership_diff(old: &Members, new: &Members) -> Vec<String> {
+    let empty = BTreeSet::new();
+    new.iter()
+        .filter_map(|(group, members)| {
+            let before = old.get(group).unwrap_or(&empty);
+            let changes: Vec<String> = members
+                .difference(before)
+                .map(|id| format!("+{id}"))
+                .chain(before.difference(members).map(|id| format!("-{id}")))
+                .collect();
+            (!changes.is_empty()).then(|| format!("{group}: {}", changes.join(", ")))
+        })
+        .collect()
+}
+
+/// Reads, validates and diffs the config and matches its groups against the known devices on a
+/// worker thread so the event loop keeps syncing meanwhile; the result arrives as a `ConfigReady`
+/// event, applied between two other events, or as a `ConfigRejected` event when the config is
+/// invalid and the current one is kept. Changes during a reload start one more reload once it is
+/// done.
+pub struct Reloader {
+    sender: Sender<VolumeSyncEvent>,
+    groups: BTreeMap<String, GroupConfig>,
+    busy: bool,
+    pending: bool,
+}
+
+impl Reloader {
+    pub fn new(sender: Sender<VolumeSyncEvent>, config: &Config) -> Reloader {
+        Reloader {
+            sender,
+            groups: config.groups(),
+            busy: false,
+            pending: false,
+        }
+    }
+
+    /// Starts a reload matching the groups against the devices `engine` knows now
+    pub fn request(&mut self, engine: &Engine) {
+        if self.busy {
+            self.pending = true;
+            return;
+        }
+        self.busy = true;
+        let old = self.groups.clone();
+        let old_members = engine.members();
+        let (generation, devices) = engine.devices();
+        thread::spawn(closure!(clone self.sender, || {
+            let _span = tracing::info_span!("reload").entered();
+            match load() {
+                Ok(config) => {
+                    let groups = config.groups();
+                    tracing::info!("groups {}", diff(&old, &groups));
+                    let members = group::members(&groups, devices.iter());
+                    for change in membership_diff(&old_members, &members) {
+                        tracing::info!("members {change}");
+                    }
+                    let _ = sender.send(VolumeSyncEvent::ConfigReady {
+                        config: Box::new(config),
+                        members,
+                        generation,
+                    });
+                }
+                Err(errors) => {
+                    log_invalid(&errors);
+                    tracing::warn!("keeping the current config");
+                    let _ = sender.send(VolumeSyncEvent::ConfigRejected);
+                }
+            }
+        }));
+    }
+
+    /// Notes that `config` is about to be applied, returning whether another reload is pending
+    pub fn finished(&mut self, config: &Config) -> bool {
+        self.groups = config.groups();
+        self.rejected()
+    }
+
+    /// Notes that the reload kept the current config, returning whether another reload is pending
+    pub fn rejected(&mut self) -> bool {
+        self.busy = false;
+        std::mem::take(&mut self.pending)
+    }
+}
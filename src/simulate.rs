@@ -0,0 +1,437 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use volume_sync_core::backend::Backend;
+use volume_sync_core::config::{Config, GroupConfig};
+use volume_sync_core::event::{VolumeSyncEvent, SHARED_CONTEXT};
+use volume_sync_core::format;
+use volume_sync_core::sink::{DeviceId, DeviceKind, SinkDetails};
+use volume_sync_core::volume::{parse_percent, percent_to_volume, VolumeSpec};
+
+/// How long the last step is given to settle before shutting down
+const SETTLE: Duration = Duration::from_millis(500);
+
+/// A sink of a scenario, e.g. `{ name = "speakers", volume = "40%" }`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FakeSink {
+    name: String,
+    volume: String,
+}
+
+/// One scripted event, exactly one of `new`, `change` and `remove`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Step {
+    /// Milliseconds to wait after the previous step
+    #[serde(default)]
+    after: u64,
+    new: Option<FakeSink>,
+    change: Option<FakeSink>,
+    remove: Option<String>,
+}
+
+/// Scripted sink events to run through the event loop in place of a sound server
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Config to run with instead of the config file
+    pub config: Option<Config>,
+    /// Sinks present from the start
+    #[serde(default)]
+    sinks: Vec<FakeSink>,
+    #[serde(default, rename = "step")]
+    steps: Vec<Step>,
+}
+
+impl Scenario {
+    pub fn read(path: &str) -> Result<Scenario, String> {
+        let content =
+            fs::read_to_string(path).map_err(|e| format!("failed to read {path}: {e}"))?;
+        let scenario: Scenario =
+            toml::from_str(&content).map_err(|e| format!("invalid scenario {path}: {e}"))?;
+        for (number, step) in scenario.steps.iter().enumerate() {
+            let events = [
+                step.new.is_some(),
+                step.change.is_some(),
+                step.remove.is_some(),
+            ];
+            if events.iter().filter(|&&event| event).count() != 1 {
+                return Err(format!(
+                    "step {} needs exactly one of new, change and remove",
+                    number + 1
+                ));
+            }
+        }
+        for sink in scenario
+            .steps
+            .iter()
+            .flat_map(|s| &s.new)
+            .chain(&scenario.sinks)
+        {
+            parse_percent(&sink.volume)?;
+        }
+        for sink in scenario.steps.iter().filter_map(|s| s.change.as_ref()) {
+            parse_percent(&sink.volume)?;
+        }
+        Ok(scenario)
+    }
+}
+
+fn details(index: u32, name: &str, volume: u32) -> SinkDetails {
+    SinkDetails {
+        index,
+        name: name.to_string(),
+        description: None,
+        card_name: None,
+        kind: DeviceKind::Sink,
+        properties: BTreeMap::new(),
+        volume,
+        mute: false,
+        active_port: None,
+        ports: Vec::new(),
+        card: None,
+        suspended: false,
+        sample_spec: String::new(),
+        rate: 0,
+        formats: Vec::new(),
+        seat: None,
+        monitor: None,
+        flat_volume: false,
+        hw_volume: false,
+        volume_steps: 0,
+    }
+}
+
+/// Sinks of the simulation by index, and the next index to hand out
+#[derive(Default)]
+struct Sinks {
+    sinks: BTreeMap<u32, SinkDetails>,
+    next: u32,
+}
+
+impl Sinks {
+    fn add(&mut self, sink: &FakeSink) -> SinkDetails {
+        let volume = percent_to_volume(parse_percent(&sink.volume).expect("checked on read"));
+        let details = details(self.next, &sink.name, volume);
+        self.sinks.insert(self.next, details.clone());
+        self.next += 1;
+        details
+    }
+
+    fn find(&mut self, name: &str) -> Option<&mut SinkDetails> {
+        self.sinks.values_mut().find(|sink| sink.name == name)
+    }
+}
+
+/// A fake sound server playing a scenario, printing every call the event loop makes on it
+pub struct Simulation {
+    sender: Sender<VolumeSyncEvent>,
+    sinks: Arc<Mutex<Sinks>>,
+    subscribed: Arc<AtomicBool>,
+    start: Instant,
+    /// Every call printed, without its time
+    recorded: Mutex<Vec<String>>,
+}
+
+impl Simulation {
+    /// Adds the initial sinks and plays the steps in the background, shutting down after the last
+    pub fn start(scenario: Scenario, sender: Sender<VolumeSyncEvent>) -> Simulation {
+        let mut sinks = Sinks::default();
+        for sink in &scenario.sinks {
+            sinks.add(sink);
+        }
+        let simulation = Simulation {
+            sender: sender.clone(),
+            sinks: Arc::new(Mutex::new(sinks)),
+            subscribed: Arc::new(AtomicBool::new(true)),
+            start: Instant::now(),
+            recorded: Mutex::new(Vec::new()),
+        };
+        thread::spawn({
+            let (sinks, start) = (simulation.sinks.clone(), simulation.start);
+            move || play(scenario.steps, sinks, sender, start)
+        });
+        let _ = simulation.sender.send(VolumeSyncEvent::ContextState(
+            SHARED_CONTEXT.to_string(),
+            "Ready".to_string(),
+        ));
+        simulation
+    }
+
+    fn record(&self, line: &str) {
+        println!("{:>6}ms {line}", self.start.elapsed().as_millis());
+        self.recorded
+            .lock()
+            .expect("failed to lock recorded calls")
+            .push(line.to_string());
+    }
+
+    /// Sets a sink's volume and reports the change like a server would
+    fn write(&self, group: &str, device: DeviceId, volume: u32) {
+        let mut sinks = self.sinks.lock().expect("failed to lock sinks");
+        let Some(sink) = sinks.sinks.get_mut(&device.index) else {
+            self.record(&format!("{group}: set missing {device}"));
+            return;
+        };
+        self.record(&format!(
+            "{group}: set {} to {}",
+            sink.name,
+            format::volume(volume)
+        ));
+        if sink.volume == volume {
+            return;
+        }
+        sink.volume = volume;
+        if self.subscribed.load(Ordering::Relaxed) {
+            let _ = self.sender.send(VolumeSyncEvent::SinkChanged(sink.clone()));
+        }
+    }
+
+    fn volume(&self, device: DeviceId) -> Option<u32> {
+        let sinks = self.sinks.lock().expect("failed to lock sinks");
+        sinks.sinks.get(&device.index).map(|sink| sink.volume)
+    }
+}
+
+/// Runs the steps of a scenario, sending the events a server would
+fn play(
+    steps: Vec<Step>,
+    sinks: Arc<Mutex<Sinks>>,
+    sender: Sender<VolumeSyncEvent>,
+    start: Instant,
+) {
+    for step in steps {
+        thread::sleep(Duration::from_millis(step.after));
+        let mut sinks = sinks.lock().expect("failed to lock sinks");
+        let (line, event) = if let Some(sink) = &step.new {
+            let details = sinks.add(sink);
+            let line = format!("new {} at {}", sink.name, sink.volume);
+            (line, Some(VolumeSyncEvent::SinkNew(details)))
+        } else if let Some(change) = &step.change {
+            let volume = percent_to_volume(parse_percent(&change.volume).expect("checked on read"));
+            let line = format!("change {} to {}", change.name, change.volume);
+            let event = sinks.find(&change.name).map(|sink| {
+                sink.volume = volume;
+                VolumeSyncEvent::SinkChanged(sink.clone())
+            });
+            (line, event)
+        } else {
+            let name = step.remove.as_deref().expect("checked on read");
+            let index = sinks.find(name).map(|sink| sink.index);
+            if let Some(index) = index {
+                sinks.sinks.remove(&index);
+            }
+            (
+                format!("remove {name}"),
+                index.map(VolumeSyncEvent::SinkRemoved),
+            )
+        };
+        drop(sinks);
+        match event {
+            Some(event) => {
+                println!("{:>6}ms > {line}", start.elapsed().as_millis());
+                let _ = sender.send(event);
+            }
+            None => println!(
+                "{:>6}ms > {line}: no such sink",
+                start.elapsed().as_millis()
+            ),
+        }
+    }
+    thread::sleep(SETTLE);
+    let _ = sender.send(VolumeSyncEvent::Shutdown);
+}
+
+impl Backend for Simulation {
+    fn configure(&mut self, _groups: &BTreeMap<String, GroupConfig>) {}
+
+    fn request_topology(&self) {
+        let sinks = self.sinks.lock().expect("failed to lock sinks");
+        let sinks = sinks.sinks.values().cloned().collect();
+        for event in [
+            VolumeSyncEvent::CardListLoaded(Vec::new()),
+            VolumeSyncEvent::SinkListLoaded(sinks),
+            VolumeSyncEvent::SourceListLoaded(Vec::new()),
+            VolumeSyncEvent::RecordingListLoaded(Vec::new()),
+            VolumeSyncEvent::StreamListLoaded(Vec::new()),
+        ] {
+            self.sender.send(event).expect("failed to send listing");
+        }
+    }
+
+    fn sync_volume(&self, group: &str, from: DeviceId, to: &[DeviceId], min_delta: u32) {
+        let Some(volume) = self.volume(from) else {
+            self.record(&format!("{group}: sync from missing {from}"));
+            return;
+        };
+        for device in to {
+            let delta = self
+                .volume(*device)
+                .map_or(u32::MAX, |v| v.abs_diff(volume));
+            if delta > 0 && delta >= min_delta {
+                self.write(group, *device, volume);
+            }
+        }
+    }
+
+    fn set_volume(&self, group: &str, devices: &[DeviceId], volume: VolumeSpec) {
+        let Some(current) = devices.first().and_then(|first| self.volume(*first)) else {
+            return;
+        };
+        let volume = volume.apply(current);
+        for device in devices {
+            self.write(group, *device, volume);
+        }
+    }
+
+    fn restore_volume(&self, group: &str, device: DeviceId, volume: u32) {
+        self.write(group, device, volume);
+    }
+
+    fn combine(&self, group: &str, sinks: &[String]) {
+        self.record(&format!("{group}: combine [{}]", sinks.join(", ")));
+    }
+
+    fn set_default_sink(&self, name: &str) {
+        self.record(&format!("default sink {name}"));
+    }
+
+    fn set_subscribed(&self, subscribed: bool) {
+        self.subscribed.store(subscribed, Ordering::Relaxed);
+    }
+
+    fn shutdown(self: Box<Self>) {
+        self.record("shutdown");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use volume_sync_core::engine::{Action, Engine, Input};
+
+    use super::*;
+
+    /// The input the event loop makes of a simulated event
+    fn input(event: VolumeSyncEvent) -> Option<Input> {
+        let input = match event {
+            VolumeSyncEvent::SinkListLoaded(sinks) => Input::SinkList(sinks),
+            VolumeSyncEvent::SinkNew(sink) => Input::SinkNew(sink),
+            VolumeSyncEvent::SinkChanged(sink) => Input::SinkChanged(sink),
+            VolumeSyncEvent::SinkRemoved(index) => Input::SinkRemoved(index),
+            VolumeSyncEvent::ContextState(context, state) => Input::ContextState { context, state },
+            _ => return None,
+        };
+        Some(input)
+    }
+
+    /// Plays `scenario` through an engine until the simulation shuts down, returning every call
+    /// recorded
+    fn play(scenario: &str) -> Vec<String> {
+        let mut scenario: Scenario = toml::from_str(scenario).expect("invalid test scenario");
+        let config = scenario
+            .config
+            .take()
+            .expect("test scenario without config");
+        let (sender, receiver) = channel();
+        let simulation = Simulation::start(scenario, sender);
+        let mut engine = Engine::new();
+        engine.handle(Input::ConfigLoaded(Box::new(config)));
+        simulation.request_topology();
+        loop {
+            let event = receiver
+                .recv_timeout(Duration::from_secs(5))
+                .expect("simulation did not shut down");
+            if let VolumeSyncEvent::Shutdown = event {
+                break;
+            }
+            let Some(input) = input(event) else {
+                continue;
+            };
+            for action in engine.handle(input) {
+                match action {
+                    Action::SyncVolume {
+                        group,
+                        from,
+                        to,
+                        min_delta,
+                    } => simulation.sync_volume(&group, from, &to, min_delta),
+                    Action::SetVolume {
+                        group,
+                        devices,
+                        volume,
+                    } => simulation.set_volume(&group, &devices, volume),
+                    Action::RestoreVolume {
+                        group,
+                        device,
+                        volume,
+                    } => simulation.restore_volume(&group, device, volume),
+                    _ => {}
+                }
+            }
+        }
+        let recorded = simulation.recorded.lock().expect("failed to lock");
+        recorded.clone()
+    }
+
+    #[test]
+    fn changes_are_written_to_the_other_members() {
+        let recorded = play(
+            r#"
+            sinks = [
+                { name = "speakers", volume = "40%" },
+                { name = "headphones", volume = "40%" },
+            ]
+
+            [config.groups.desk]
+            sinks = ["speakers", "headphones", "dac"]
+
+            [[step]]
+            after = 50
+            change = { name = "speakers", volume = "60%" }
+
+            [[step]]
+            after = 50
+            new = { name = "dac", volume = "20%" }
+
+            [[step]]
+            after = 50
+            remove = "headphones"
+
+            [[step]]
+            after = 50
+            change = { name = "dac", volume = "30%" }
+            "#,
+        );
+        assert_eq!(
+            recorded,
+            vec![
+                "desk: set headphones to 60%",
+                "desk: set dac to 60%",
+                "desk: set speakers to 30%",
+            ]
+        );
+    }
+
+    #[test]
+    fn steps_need_exactly_one_event() {
+        let path =
+            std::env::temp_dir().join(format!("volume-sync-scenario-{}.toml", std::process::id()));
+        fs::write(&path, "[[step]]\nafter = 10\n").expect("failed to write scenario");
+        let result = Scenario::read(&path.to_string_lossy());
+        let _ = fs::remove_file(&path);
+        assert_eq!(
+            result.err(),
+            Some("step 1 needs exactly one of new, change and remove".to_string())
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use closure::closure;
+
+use notify::{EventKind, PollWatcher, RecursiveMode, Watcher};
+
+use volume_sync_core::config;
+use volume_sync_core::event::VolumeSyncEvent;
+
+/// Quiet time after the last change before reloading, so editors saving through a chain of
+/// renames, creates and chmods cause a single reload
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Restarts of the native watcher before falling back to polling
+const MAX_RESTARTS: u32 = 3;
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Watches the config file and drop-in directory, following symlinks to their real location.
+/// Watcher errors are reported as `WatcherError` events, on which the watcher is restarted and
+/// eventually replaced by a polling one.
+pub struct ConfigWatcher {
+    sender: Sender<VolumeSyncEvent>,
+    changed: Sender<()>,
+    watcher: Box<dyn Watcher>,
+    polling: bool,
+    restarts: u32,
+    last_error: Option<String>,
+    watched: Vec<PathBuf>,
+    paths: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+/// The config file and drop-in directory, plus their targets when they are symlinks
+fn config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for path in [config::get_file(), config::get_dir()] {
+        let path = PathBuf::from(path);
+        if let Ok(real) = fs::canonicalize(&path) {
+            if real != path {
+                paths.push(real);
+            }
+        }
+        paths.push(path);
+    }
+    paths
+}
+
+/// The closest directory to `path` that exists
+fn existing_ancestor(path: &Path) -> Option<PathBuf> {
+    path.ancestors().find(|p| p.is_dir()).map(Path::to_path_buf)
+}
+
+/// Directories to watch so every change to `paths` is seen
+fn watch_dirs(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    for path in paths {
+        let targets = [Some(path.as_path()), path.parent()];
+        for dir in targets.into_iter().flatten() {
+            if let Some(dir) = existing_ancestor(dir) {
+                if !dirs.contains(&dir) {
+                    dirs.push(dir);
+                }
+            }
+        }
+    }
+    dirs
+}
+
+/// Whether `path` is one of the config paths, a fragment inside the drop-in directory or a missing
+/// directory on the way to them
+fn is_relevant(paths: &[PathBuf], path: &Path) -> bool {
+    paths
+        .iter()
+        .any(|p| path == p || path.parent() == Some(p) || p.starts_with(path))
+}
+
+fn create(
+    paths: &Arc<Mutex<Vec<PathBuf>>>,
+    changed: &Sender<()>,
+    sender: &Sender<VolumeSyncEvent>,
+    polling: bool,
+) -> notify::Result<Box<dyn Watcher>> {
+    let handler = closure!(
+        clone paths,
+        clone changed,
+        clone sender,
+        |res: notify::Result<notify::Event>| match res {
+            Ok(event) => {
+                let paths = paths.lock().expect("failed to lock config paths");
+                let relevant = event.paths.iter().any(|p| is_relevant(&paths, p));
+                match event.kind {
+                    EventKind::Access(_) => {}
+                    _ if relevant => {
+                        tracing::debug!("event: {event:?}");
+                        let _ = changed.send(());
+                    }
+                    _ => tracing::trace!("ignore event: {event:?}"),
+                }
+            }
+            Err(e) => {
+                tracing::error!("config watcher error: {e:?}");
+                let _ = sender.send(VolumeSyncEvent::WatcherError(e.to_string()));
+            }
+        }
+    );
+    if polling {
+        let config = notify::Config::default().with_poll_interval(POLL_INTERVAL);
+        Ok(Box::new(PollWatcher::new(handler, config)?))
+    } else {
+        Ok(Box::new(notify::recommended_watcher(handler)?))
+    }
+}
+
+impl ConfigWatcher {
+    pub fn start(sender: Sender<VolumeSyncEvent>) -> notify::Result<ConfigWatcher> {
+        let config_file = PathBuf::from(config::get_file());
+        if let Some(dir) = config_file.parent() {
+            if let Err(e) = fs::create_dir_all(dir) {
+                tracing::warn!("failed to create config directory {}: {e}", dir.display());
+            }
+        }
+
+        let (changed, receiver) = channel::<()>();
+        thread::spawn(closure!(clone sender, || {
+            while receiver.recv().is_ok() {
+                loop {
+                    match receiver.recv_timeout(DEBOUNCE) {
+                        Ok(()) => continue,
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                sender
+                    .send(VolumeSyncEvent::ConfigChanged)
+                    .expect("failed to send config event");
+            }
+        }));
+
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let (watcher, polling) = match create(&paths, &changed, &sender, false) {
+            Ok(watcher) => (watcher, false),
+            Err(e) => {
+                tracing::warn!("failed to create config watcher, polling instead: {e}");
+                (create(&paths, &changed, &sender, true)?, true)
+            }
+        };
+
+        let mut config_watcher = ConfigWatcher {
+            sender,
+            changed,
+            watcher,
+            polling,
+            restarts: 0,
+            last_error: None,
+            watched: Vec::new(),
+            paths,
+        };
+        config_watcher.refresh();
+        Ok(config_watcher)
+    }
+
+    /// Replaces the watcher after an `error`, with a polling one once restarts keep failing
+    pub fn restart(&mut self, error: String) {
+        self.restarts += 1;
+        self.last_error = Some(error);
+        let polling = self.polling || self.restarts >= MAX_RESTARTS;
+        tracing::warn!(
+            "restarting config watcher ({} of {MAX_RESTARTS}{})",
+            self.restarts,
+            if polling { ", polling" } else { "" }
+        );
+        match create(&self.paths, &self.changed, &self.sender, polling) {
+            Ok(watcher) => {
+                self.watcher = watcher;
+                self.polling = polling;
+                self.watched.clear();
+                self.refresh();
+            }
+            Err(e) => {
+                tracing::error!("failed to restart config watcher: {e}");
+                self.last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// One line summary for `status`
+    pub fn health(&self) -> String {
+        let kind = if self.polling { "polling" } else { "native" };
+        match &self.last_error {
+            Some(e) => format!("{kind}, {} restarts, last error: {e}", self.restarts),
+            None => format!("{kind}, ok"),
+        }
+    }
+
+    /// Re-resolves symlinks and watches whatever directories now lead to the config
+    pub fn refresh(&mut self) {
+        let paths = config_paths();
+        let dirs = watch_dirs(&paths);
+        *self.paths.lock().expect("failed to lock config paths") = paths;
+        if dirs == self.watched {
+            return;
+        }
+
+        for dir in &self.watched {
+            let _ = self.watcher.unwatch(dir);
+        }
+        self.watched.clear();
+        let mut failed = None;
+        for dir in dirs {
+            match self.watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    tracing::info!("watching {} for config changes", dir.display());
+                    self.watched.push(dir);
+                }
+                Err(e) => {
+                    tracing::warn!("failed to watch {}: {e}", dir.display());
+                    failed = Some(e.to_string());
+                }
+            }
+        }
+        if let Some(e) = failed {
+            if !self.polling {
+                self.restart(e);
+            }
+        }
+    }
+}